@@ -6,6 +6,15 @@ use solana_program::{entrypoint::ProgramResult, program_error::ProgramError};
 pub enum Error {
     InitializeQuizWinnerIsNotInList,
     Overflow,
+    OnlyPendingOwnerCanAcceptOwnership,
+    ClaimOnlyToWinnerAssociatedTokenAddress,
+    ClaimQuizIsLocked,
+    TooManyWinners,
+    InitializeQuizViewersMustMatchWinners,
+    InitializeQuizMustHaveWinners,
+    InitializeQuizPointsMustNotBeZero,
+    InitializeQuizWinnersMustBeUnique,
+    ClaimQuizMustBeMigrated,
 }
 
 impl Error {
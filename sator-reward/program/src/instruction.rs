@@ -24,12 +24,21 @@ pub struct InitializeViewerInput {
     pub user: Pubkey,
 }
 
+#[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema, Clone)]
+pub struct ProposeOwnerInput {
+    /// default pubkey cancels proposal
+    pub pending_owner: Pubkey,
+}
+
 #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Clone)]
 pub enum Instruction {
     InitializeShow(InitializeShowInput),
     InitializeViewer(InitializeViewerInput),
     InitializeQuiz(InitializeQuizInput),
     Claim,
+    ProposeOwner(ProposeOwnerInput),
+    AcceptOwner,
+    Migrate,
 }
 
 /// Creates [Instruction::InitializeShow] instruction which initializes `show` and shows' `token_account`
@@ -112,15 +121,28 @@ pub struct WinnerInput {
 #[repr(C)]
 #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default, Clone)]
 pub struct InitializeQuizInput {
-    /// less than or equal to 5
+    /// less than or equal to [crate::state::Quiz::MAX_WINNERS], use [initialize_quiz_pages] for more
     pub winners: Vec<WinnerInput>,
     /// amount of tokens to distribute for this quiz
     pub amount: TokenAmount,
 }
 
+impl InitializeQuizInput {
+    /// no owner is listed in `winners` twice
+    pub fn has_unique_winners(&self) -> bool {
+        self.winners
+            .iter()
+            .enumerate()
+            .all(|(index, winner)| self.winners[..index].iter().all(|x| x.owner != winner.owner))
+    }
+}
+
 /// Creates [Instruction::InitializeQuiz] instruction which initializes `quiz` with results. Validates winner is viewer.
 /// `show`'s `quizzes` latest number must be provided.
-/// Winners and viewers must be in same corresponding order (zip should work), and less or equal to 5.
+/// Winners and viewers must be in same corresponding order (zip should work), and less or equal to [crate::state::Quiz::MAX_WINNERS].
+/// Fails if there are no winners or all winners have zero points, as nobody could claim `amount`, or if any winner is listed twice.
+/// Each viewer must be derived from corresponding winner, so only registered viewers can win.
+/// `amount` is shared by points of winners of this quiz only, so results paged by caller must split amount by points of each page, as [initialize_quiz_pages] does.
 ///
 /// Instruction does not forces specified locked amount to be presented on on `Show::token_account` which is risk for user will not be payed.
 /// Accounts:
@@ -171,24 +193,115 @@ pub fn initialize_quiz(
     ))
 }
 
+/// Count of winners with viewers which [initialize_quiz] fits into single transaction
+pub const QUIZ_PAGE_WINNERS: usize = crate::state::Quiz::MAX_WINNERS;
+
+/// Creates [Instruction::InitializeQuiz] instructions for each page of [QUIZ_PAGE_WINNERS] `winners`, each page is quiz of next index.
+/// `input.amount` is split between pages by winner points, so winner gets same share as from single quiz.
+/// Instructions must be executed in order, each in own transaction.
+/// Each page must have winner with points, so winners with zero points should go last.
+/// Fails if count of `winners` is not same as of `input.winners`, or if any winner is listed twice, as pages cannot check each other.
+///
+/// Accounts are same as for [initialize_quiz].
+pub fn initialize_quiz_pages(
+    owner: &SignerPubkey,
+    show: &Pubkey,
+    show_quizzes_index: u16,
+    winners: Vec<Pubkey>,
+    input: InitializeQuizInput,
+) -> Result<Vec<solana_program::instruction::Instruction>, ProgramError> {
+    if winners.len() != input.winners.len() {
+        return Err(crate::errors::Error::InitializeQuizViewersMustMatchWinners.into());
+    }
+    if !input.has_unique_winners() {
+        return Err(crate::errors::Error::InitializeQuizWinnersMustBeUnique.into());
+    }
+    let total_points: u128 = input.winners.iter().map(|x| x.points as u128).sum();
+    let pages = input.winners.chunks(QUIZ_PAGE_WINNERS).count();
+    let mut unallocated = input.amount;
+    input
+        .winners
+        .chunks(QUIZ_PAGE_WINNERS)
+        .zip(winners.chunks(QUIZ_PAGE_WINNERS))
+        .enumerate()
+        .map(|(page, (page_winners, page_viewers))| {
+            let page_points: u128 = page_winners.iter().map(|x| x.points as u128).sum();
+            let amount = if page + 1 == pages {
+                unallocated
+            } else {
+                (input.amount as u128 * page_points)
+                    .checked_div(total_points)
+                    .unwrap_or_default() as TokenAmount
+            };
+            unallocated -= amount;
+            initialize_quiz(
+                owner,
+                show,
+                show_quizzes_index + page as u16,
+                page_viewers.to_vec(),
+                InitializeQuizInput {
+                    winners: page_winners.to_vec(),
+                    amount,
+                },
+            )
+        })
+        .collect()
+}
+
 /// Creates [Instruction::Claim] wins on behalf of user. Transfers tokens from show account to user account, sets win claimed.
+/// Anybody can claim on behalf of user, but only to associated token account of `user_wallet_winner` for show mint.
+/// Fails if any of `quizzes` is still locked, or is of older [sator_sdk::state::StateVersion] not upgraded by [migrate] yet.
 ///
 /// Accounts:
+///  * `sysvar_clock`       - *program, implicit* to check `quizzes` are not locked
 ///  * `spl_token`          -
-///  * `owner`              - *signer, payer* and owner of `show`.
-///  * `show`               - used to validate `owner` and `quiz`
+///  * `fee_payer`          - *signer, payer* anybody
+///  * `show`               - used to validate `quiz`
 ///  * `show_authority`     - *implicit* program derived account from `32 bytes show public key` based `program_id`.
-//   * `winner`             - user who got win points and stored in quiz data, will be find in each quiz
+//   * `winner`             - user who got win points and stored in quiz data, will be find in each quiz, if *signer* then `user_token_account` is not checked
 //   * `show_token_account` - *derived* source of tokens to transfer to `winner`
-//   * `user_token_account` - destination
+//   * `user_token_account` - destination, associated token account of `winner` unless `winner` signs
 ///  * `quizzes`            - *mutable, derived* to claim rewards from
 #[allow(clippy::too_many_arguments)]
 pub fn claim(
-    owner: &SignerPubkey,
-    show: &SignerPubkey,
+    fee_payer: &SignerPubkey,
+    show: &Pubkey,
     user_wallet_winner: &Pubkey,
     user_token_account: &TokenAccountPubkey,
     quizes: Vec<Pubkey>,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    claim_instruction(
+        fee_payer,
+        show,
+        AccountMeta::new(*user_wallet_winner, false),
+        user_token_account,
+        quizes,
+    )
+}
+
+/// Creates [Instruction::Claim] signed by `user_wallet_winner`, so that tokens go to any `user_token_account` of winner choice.
+/// Accounts are same as for [claim] with `user_wallet_winner` as `fee_payer`.
+pub fn claim_by_winner(
+    user_wallet_winner: &SignerPubkey,
+    show: &Pubkey,
+    user_token_account: &TokenAccountPubkey,
+    quizes: Vec<Pubkey>,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    claim_instruction(
+        user_wallet_winner,
+        show,
+        AccountMeta::new(*user_wallet_winner, true),
+        user_token_account,
+        quizes,
+    )
+}
+
+fn claim_instruction(
+    fee_payer: &SignerPubkey,
+    show: &Pubkey,
+    user_wallet_winner: AccountMeta,
+    user_token_account: &TokenAccountPubkey,
+    quizes: Vec<Pubkey>,
 ) -> Result<solana_program::instruction::Instruction, ProgramError> {
     let show_authority = Pubkey::find_program_address_for_pubkey(show, &program_id());
     let show_token_account =
@@ -202,11 +315,12 @@ pub fn claim(
         &Instruction::Claim,
         [
             vec![
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
                 AccountMeta::new_readonly(spl_token::id(), false),
-                AccountMeta::new_readonly(*owner, true),
+                AccountMeta::new_readonly(*fee_payer, true),
                 AccountMeta::new_readonly(*show, false),
                 AccountMeta::new_readonly(show_authority.0, false),
-                AccountMeta::new(*user_wallet_winner, false),
+                user_wallet_winner,
                 AccountMeta::new(show_token_account, false),
                 AccountMeta::new(*user_token_account, false),
             ],
@@ -215,3 +329,77 @@ pub fn claim(
         .concat(),
     ))
 }
+
+/// Creates [Instruction::ProposeOwner] instruction which stores `input.pending_owner` on `show`.
+/// Ownership is not changed until [Instruction::AcceptOwner] signed by `input.pending_owner`.
+///
+/// Accounts:
+///  * `owner` - *signer* current owner of `show`
+///  * `show`  - *mutable* initialized show
+pub fn propose_owner(
+    owner: &SignerPubkey,
+    show: &Pubkey,
+    input: ProposeOwnerInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::ProposeOwner(input),
+        vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*show, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::AcceptOwner] instruction which makes `pending_owner` owner of `show` and clears proposal.
+///
+/// Accounts:
+///  * `pending_owner` - *signer* owner proposed by [Instruction::ProposeOwner]
+///  * `show`          - *mutable* initialized show
+pub fn accept_owner(
+    pending_owner: &SignerPubkey,
+    show: &Pubkey,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::AcceptOwner,
+        vec![
+            AccountMeta::new_readonly(*pending_owner, true),
+            AccountMeta::new(*show, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::Migrate] instruction which upgrades `show` and `quizzes` of older [sator_sdk::state::StateVersion] in place.
+/// Accounts of current version are left as is. Anybody can migrate as data is kept.
+///
+/// Accounts:
+///  * `system_program` - *program, implicit* to top up rent of grown account
+///  * `sysvar_rent`    - *program, implicit*
+///  * `fee_payer`      - *signer, mutable* pays rent of grown account
+///  * `show`           - *mutable* show
+///  * `quizzes`        - *mutable, derived* from `show`
+pub fn migrate(
+    fee_payer: &SignerPubkey,
+    show: &Pubkey,
+    quizes: Vec<Pubkey>,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let quizes = quizes
+        .into_iter()
+        .map(|x| AccountMeta::new(x, false))
+        .collect();
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::Migrate,
+        [
+            vec![
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new(*fee_payer, true),
+                AccountMeta::new(*show, false),
+            ],
+            quizes,
+        ]
+        .concat(),
+    ))
+}
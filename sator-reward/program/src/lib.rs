@@ -2,8 +2,11 @@
 //! Reward for viewers.
 //! 1. Creator initializes show
 //! 2. Creator sets prove user can participate in quiz
-//! 3. Quiz results are put into contract
-//! 4. After some lock time, it is possible to claim reward from each winner quiz
+//! 3. Quiz results are put into contract, large results are paged into several quizzes
+//! 4. After some lock time (`Quiz.locked_until`), it is possible to claim reward from each winner quiz
+//!    - by anybody to winner associated token account for show mint or by winner signature to any token account
+//! 5. Creator can hand over show: proposes new owner, which accepts ownership by signature
+//! 6. Show and quizzes of older state version are upgraded in place by `Migrate`
 //!
 //! Derivation rules:
 //!```rust, ignore
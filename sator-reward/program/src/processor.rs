@@ -9,7 +9,7 @@ use sator_sdk::borsh::*;
 use sator_sdk::invoke::{self, ProgramPubkeySignature};
 use sator_sdk::is_owner;
 use sator_sdk::program::*;
-use sator_sdk::state::StateVersion;
+use sator_sdk::state::{migrate_state, StateVersion, VersionedState};
 use sator_sdk::types::*;
 use solana_program::clock::Clock;
 use solana_program::msg;
@@ -21,7 +21,9 @@ use solana_program::{
     account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, pubkey::Pubkey,
 };
 
-use crate::instruction::{InitializeQuizInput, InitializeViewerInput, Instruction};
+use crate::instruction::{
+    InitializeQuizInput, InitializeViewerInput, Instruction, ProposeOwnerInput,
+};
 use crate::state::*;
 use crate::types::Winner;
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -86,13 +88,13 @@ pub fn process_instruction(
             _ => Err(ProgramError::NotEnoughAccountKeys),
         },
         Instruction::Claim => match accounts {
-            [spl_token, show_owner, show, show_authority, user_wallet_winner, show_token_account, user_token_account, ..] =>
+            [clock, spl_token, _fee_payer, show, show_authority, user_wallet_winner, show_token_account, user_token_account, ..] =>
             {
-                let quizes = accounts.iter().skip(7);
+                let quizes = accounts.iter().skip(8);
                 claim(
                     program_id,
+                    clock,
                     spl_token,
-                    show_owner,
                     show,
                     show_authority,
                     user_wallet_winner,
@@ -103,13 +105,99 @@ pub fn process_instruction(
             }
             _ => Err(ProgramError::NotEnoughAccountKeys),
         },
+        Instruction::ProposeOwner(input) => match accounts {
+            [owner, show, ..] => propose_owner(program_id, owner, show, input),
+            _ => Err(ProgramError::NotEnoughAccountKeys),
+        },
+        Instruction::AcceptOwner => match accounts {
+            [pending_owner, show, ..] => accept_owner(program_id, pending_owner, show),
+            _ => Err(ProgramError::NotEnoughAccountKeys),
+        },
+        Instruction::Migrate => match accounts {
+            [system_program, sysvar_rent, fee_payer, show, ..] => {
+                let quizes = accounts.iter().skip(4);
+                migrate(
+                    program_id,
+                    system_program,
+                    sysvar_rent,
+                    fee_payer,
+                    show,
+                    quizes,
+                )
+            }
+            _ => Err(ProgramError::NotEnoughAccountKeys),
+        },
+    }
+}
+
+fn propose_owner(
+    program_id: &Pubkey,
+    owner: &AccountInfo,
+    show: &AccountInfo,
+    input: ProposeOwnerInput,
+) -> ProgramResult {
+    show.is_owner(program_id)?;
+    let mut show_state = show.deserialize::<Show>()?;
+    show_state.initialized()?;
+    owner.is_signer()?;
+    is_owner!(owner, show_state);
+
+    show_state.pending_owner = input.pending_owner;
+    show_state.serialize_const(&mut *show.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn accept_owner(program_id: &Pubkey, pending_owner: &AccountInfo, show: &AccountInfo) -> ProgramResult {
+    show.is_owner(program_id)?;
+    let mut show_state = show.deserialize::<Show>()?;
+    show_state.initialized()?;
+    pending_owner.is_signer()?;
+    if show_state.pending_owner == Pubkey::default()
+        || show_state.pending_owner != pending_owner.pubkey()
+    {
+        return crate::errors::Error::OnlyPendingOwnerCanAcceptOwnership.into();
+    }
+
+    show_state.owner = show_state.pending_owner;
+    show_state.pending_owner = Pubkey::default();
+    show_state.serialize_const(&mut *show.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn migrate<'a>(
+    program_id: &Pubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    show: &AccountInfo<'a>,
+    quizes: std::iter::Skip<std::slice::Iter<AccountInfo<'a>>>,
+) -> ProgramResult {
+    show.is_owner(program_id)?;
+    fee_payer.is_signer()?;
+    let rent = Rent::from_account_info(sysvar_rent)?;
+    migrate_state::<Show>(system_program, fee_payer, show, &rent)?;
+
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), program_id);
+    for quiz in quizes {
+        quiz.is_owner(program_id)?;
+        let quiz_state = Quiz::deserialize_versioned(&quiz.try_borrow_data()?)?;
+        let (quiz_pubkey, _) = Pubkey::create_with_seed_index(
+            &show_authority_pubkey,
+            Show::QUIZES,
+            quiz_state.index as u64,
+            &program_id,
+        )?;
+        is_derived(quiz_pubkey, quiz)?;
+        migrate_state::<Quiz>(system_program, fee_payer, quiz, &rent)?;
     }
+    Ok(())
 }
 
 fn claim<'a>(
     program_id: &Pubkey,
+    clock: &AccountInfo<'a>,
     spl_token: &AccountInfo<'a>,
-    show_owner: &AccountInfo<'a>,
     show: &AccountInfo<'a>,
     show_authority: &AccountInfo<'a>,
     winner: &AccountInfo<'a>,
@@ -117,16 +205,40 @@ fn claim<'a>(
     user_token_account: &AccountInfo<'a>,
     quizes: std::iter::Skip<std::slice::Iter<AccountInfo<'a>>>,
 ) -> ProgramResult {
-    let mut show_state = show.deserialize::<Show>()?;
+    show.is_owner(program_id)?;
+    let show_state = show.deserialize::<Show>()?;
     show_state.initialized()?;
-    is_owner!(show_owner, show_state);
-    show_owner.is_signer()?;
 
     let (show_authority_pubkey, bump_seed) =
         Pubkey::find_program_address_for_pubkey(&show.pubkey(), program_id);
+    let show_token_account_pubkey = Pubkey::create_with_seed(
+        &show_authority_pubkey,
+        Show::TOKEN_ACCOUNT,
+        &spl_token::id(),
+    )?;
+    is_derived(show_authority_pubkey, show_authority)?;
+    is_derived(show_token_account_pubkey, show_token_account)?;
     let authority_signature = ProgramPubkeySignature::new(show, bump_seed);
 
+    // winner pulls own reward to any account, otherwise anybody pays to winner associated token account
+    if !winner.is_signer {
+        let token_account_data =
+            spl_token::state::Account::unpack(&show_token_account.try_borrow_data()?)?;
+        let associated_token_address = spl_associated_token_account::get_associated_token_address(
+            &winner.pubkey(),
+            &token_account_data.mint,
+        );
+        if associated_token_address != user_token_account.pubkey() {
+            return crate::errors::Error::ClaimOnlyToWinnerAssociatedTokenAddress.into();
+        }
+    }
+
+    let clock = Clock::from_account_info(clock)?;
     for quiz in quizes {
+        // older layout would be misread, so it must be migrated first
+        if StateVersion::read(&quiz.try_borrow_data()?)? != Quiz::VERSION {
+            return crate::errors::Error::ClaimQuizMustBeMigrated.into();
+        }
         let mut quiz_state = quiz.deserialize::<Quiz>()?;
         let (quiz_pubkey, _) = Pubkey::create_with_seed_index(
             &show_authority_pubkey,
@@ -136,7 +248,10 @@ fn claim<'a>(
         )?;
         is_derived(quiz_pubkey, quiz)?;
         quiz.is_owner(program_id)?;
-        let total_points: u32 = quiz_state.winners.iter().map(|x| x.points).sum();
+        if clock.unix_timestamp < quiz_state.locked_until {
+            return crate::errors::Error::ClaimQuizIsLocked.into();
+        }
+        let total_points: u64 = quiz_state.winners.iter().map(|x| x.points as u64).sum();
 
         if let Some(winner) = quiz_state
             .winners
@@ -150,7 +265,7 @@ fn claim<'a>(
                 .amount
                 .checked_mul(winner.points as u64)
                 .ok_or(ProgramError::Custom(crate::errors::Error::Overflow.into()))?
-                / total_points as u64;
+                / total_points;
 
             invoke::spl_token_transfer_signed(
                 spl_token,
@@ -199,8 +314,40 @@ fn initialize_quiz<'a>(
     is_derived(show_authority_pubkey, show_authority)?;
     is_derived(quiz_pubkey, quiz)?;
 
+    if input.winners.is_empty() {
+        return crate::errors::Error::InitializeQuizMustHaveWinners.into();
+    }
+    if input.winners.len() > Quiz::MAX_WINNERS {
+        return crate::errors::Error::TooManyWinners.into();
+    }
+    if input.winners.len() != viewers.len() {
+        return crate::errors::Error::InitializeQuizViewersMustMatchWinners.into();
+    }
+    // claim shares `amount` by points
+    if input.winners.iter().all(|x| x.points == 0) {
+        return crate::errors::Error::InitializeQuizPointsMustNotBeZero.into();
+    }
+    // winner listed twice would claim share of points once
+    if !input.has_unique_winners() {
+        return crate::errors::Error::InitializeQuizWinnersMustBeUnique.into();
+    }
+    for (winner, viewer) in input.winners.iter().zip(viewers) {
+        let (viewer_pubkey, _) = Pubkey::create_with_seed_for_pubkey(
+            &show_authority_pubkey,
+            &winner.owner,
+            &program_id,
+        )?;
+        if viewer_pubkey != viewer.pubkey() {
+            return crate::errors::Error::InitializeQuizWinnerIsNotInList.into();
+        }
+        viewer.is_owner(program_id)?;
+        let viewer = viewer.deserialize::<Viewer>()?;
+        viewer.initialized()?;
+    }
+
     let rent_state = Rent::from_account_info(sysvar_rent)?;
-    let lamports = rent_state.minimum_balance(Quiz::LEN);
+    let quiz_len = Quiz::len(input.winners.len());
+    let lamports = rent_state.minimum_balance(quiz_len);
 
     invoke::create_account_with_seed_signed(
         system_program,
@@ -209,45 +356,27 @@ fn initialize_quiz<'a>(
         show_authority,
         &seed[..],
         lamports,
-        Quiz::LEN as u64,
+        quiz_len as u64,
         program_id,
         &authority_signature,
     )?;
 
-    let winners_pubkeys: Vec<_> = input.winners.iter().map(|x| x.owner).collect();
-
-    let mut quiz_state = quiz.deserialize::<Quiz>()?;
-    let winners_state: Vec<_> = input
-        .winners
-        .into_iter()
-        .enumerate()
-        .map(|(i, x)| Winner {
-            claimed: false,
-            user_wallet: winners_pubkeys[i],
-            points: x.points,
-        })
-        .collect();
-    quiz_state.winners = <_>::default();
-    for (i, winner) in winners_state.into_iter().enumerate() {
-        quiz_state.winners[i] = winner;
-    }
-    quiz_state.uninitialized()?;
-    quiz_state.index = show_state.quizes_index;
-    quiz_state.amount = input.amount;
-    quiz_state.version = StateVersion::V1;
-    for (i, viewer) in viewers.into_iter().enumerate() {
-        let (viewer_pubkey, _) = Pubkey::create_with_seed_for_pubkey(
-            &show_authority_pubkey,
-            &winners_pubkeys[i],
-            &program_id,
-        )?;
-        viewer.is_owner(program_id)?;
-        let viewer = viewer.deserialize::<Viewer>()?;
-        viewer.initialized()?;
-    }
-
     let clock = Clock::from_account_info(clock)?;
-    quiz_state.locked_until = clock.unix_timestamp + show_state.lock_time;
+    let quiz_state = Quiz {
+        version: StateVersion::V2,
+        winners: input
+            .winners
+            .into_iter()
+            .map(|x| Winner {
+                claimed: false,
+                user_wallet: x.owner,
+                points: x.points,
+            })
+            .collect(),
+        locked_until: clock.unix_timestamp + show_state.lock_time,
+        index: show_state.quizes_index,
+        amount: input.amount,
+    };
     quiz_state.serialize_const(&mut *quiz.try_borrow_mut_data()?)?;
 
     show_state.quizes_index += 1;
@@ -367,7 +496,7 @@ fn initialize_show<'a>(
     state.uninitialized()?;
     state.lock_time = input.reward_lock_time;
     state.owner = owner.pubkey();
-    state.version = StateVersion::V1;
+    state.version = StateVersion::V2;
     state.serialize_const(&mut *show.try_borrow_mut_data()?)?;
 
     Ok(())
@@ -4,7 +4,7 @@ use std::ops::Mul;
 use std::time::Duration;
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
-use sator_sdk::state::StateVersion;
+use sator_sdk::state::{StateVersion, VersionedState};
 use sator_sdk::types::{ApproximateSeconds, SignerPubkey, TokenAccountPubkey, TokenAmount};
 use solana_program::clock::UnixTimestamp;
 use solana_program::pubkey::Pubkey;
@@ -23,6 +23,18 @@ pub struct Show {
     pub quizes_index: u16,
     ///  owner of the show
     pub owner: SignerPubkey,
+    /// proposed by `owner` to take over the show, default if none
+    pub pending_owner: Pubkey,
+}
+
+/// [Show] layout of [StateVersion::V1]
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct ShowV1 {
+    pub version: StateVersion,
+    pub lock_time: ApproximateSeconds,
+    pub quizes_index: u16,
+    pub owner: SignerPubkey,
 }
 
 /// derived from wallet + show
@@ -32,10 +44,22 @@ pub struct Viewer {
     pub version: StateVersion,
 }
 
-/// Up to N winners with points, derived from show + counter. '
+/// Winners with points, derived from show + counter. Sized by count of winners on creation, see [Quiz::len].
+/// Larger results are paged into several quizzes, each page is independent quiz sharing own `amount` by points of own winners only.
 #[repr(C)]
 #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
 pub struct Quiz {
+    pub version: StateVersion,
+    pub winners: Vec<Winner>,
+    pub locked_until: UnixTimestamp,
+    pub index: u16,
+    pub amount: TokenAmount,
+}
+
+/// [Quiz] layout of [StateVersion::V1], not used winner slots are default
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct QuizV1 {
     pub version: StateVersion,
     pub winners: [Winner; 5],
     pub locked_until: UnixTimestamp,
@@ -44,7 +68,7 @@ pub struct Quiz {
 }
 
 impl Show {
-    pub const LEN: usize = 43;
+    pub const LEN: usize = 75;
     pub const TOKEN_ACCOUNT: &'static str = "Show::token_account";
     pub const QUIZES: &'static str = "Show::quizes";
 
@@ -65,6 +89,33 @@ impl Show {
     }
 }
 
+impl ShowV1 {
+    pub const LEN: usize = 43;
+}
+
+impl VersionedState for Show {
+    const VERSION: StateVersion = StateVersion::V2;
+    const LEN: usize = Show::LEN;
+
+    fn deserialize_versioned(data: &[u8]) -> Result<Self, ProgramError> {
+        match StateVersion::read(data)? {
+            StateVersion::Uninitialized => Err(ProgramError::UninitializedAccount),
+            StateVersion::V1 => {
+                let state = ShowV1::try_from_slice(data)?;
+                Ok(Self {
+                    version: Self::VERSION,
+                    lock_time: state.lock_time,
+                    quizes_index: state.quizes_index,
+                    owner: state.owner,
+                    pending_owner: Pubkey::default(),
+                })
+            }
+            StateVersion::V2 => Ok(Self::try_from_slice(data)?),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
 impl Viewer {
     pub const LEN: usize = 1;
     pub fn uninitialized(&self) -> ProgramResult {
@@ -85,7 +136,14 @@ impl Viewer {
 }
 
 impl Quiz {
-    pub const LEN: usize = 204;
+    /// winners with viewers which fit into single transaction, larger results are paged into several quizzes
+    pub const MAX_WINNERS: usize = 10;
+
+    /// size of quiz with `capacity` winners
+    pub const fn len(capacity: usize) -> usize {
+        23 + capacity * Winner::LEN
+    }
+
     pub fn uninitialized(&self) -> ProgramResult {
         if self.version == StateVersion::Uninitialized {
             Ok(())
@@ -103,18 +161,103 @@ impl Quiz {
     }
 }
 
+impl QuizV1 {
+    pub const LEN: usize = 204;
+}
+
+impl VersionedState for Quiz {
+    const VERSION: StateVersion = StateVersion::V2;
+    const LEN: usize = Quiz::len(0);
+
+    fn deserialize_versioned(data: &[u8]) -> Result<Self, ProgramError> {
+        match StateVersion::read(data)? {
+            StateVersion::Uninitialized => Err(ProgramError::UninitializedAccount),
+            StateVersion::V1 => {
+                let state = QuizV1::try_from_slice(data)?;
+                Ok(Self {
+                    version: Self::VERSION,
+                    winners: state
+                        .winners
+                        .iter()
+                        .filter(|x| x.user_wallet != Pubkey::default())
+                        .copied()
+                        .collect(),
+                    locked_until: state.locked_until,
+                    index: state.index,
+                    amount: state.amount,
+                })
+            }
+            StateVersion::V2 => Ok(Self::try_from_slice(data)?),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    fn packed_len(&self) -> usize {
+        Quiz::len(self.winners.len())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::state::*;
     use borsh::*;
+    use sator_sdk::state::{StateVersion, VersionedState};
+    use solana_program::pubkey::Pubkey;
 
     #[test]
     fn test() {
         let data = Quiz::default().try_to_vec().unwrap();
-        assert_eq!(data.len(), Quiz::LEN);
+        assert_eq!(data.len(), Quiz::len(0));
+        let quiz = Quiz {
+            winners: vec![<_>::default(); 3],
+            ..<_>::default()
+        };
+        assert_eq!(quiz.try_to_vec().unwrap().len(), Quiz::len(3));
+        assert_eq!(quiz.packed_len(), Quiz::len(3));
+        assert!(Quiz::len(Quiz::MAX_WINNERS) <= 10 * 1024);
+        let data = QuizV1::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), QuizV1::LEN);
         let data = Show::default().try_to_vec().unwrap();
         assert_eq!(data.len(), Show::LEN);
         let data = Viewer::default().try_to_vec().unwrap();
         assert_eq!(data.len(), Viewer::LEN);
+        let data = ShowV1::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), ShowV1::LEN);
+    }
+
+    #[test]
+    fn deserialize_versioned() {
+        let owner = Pubkey::new_unique();
+        let show = ShowV1 {
+            version: StateVersion::V1,
+            lock_time: 60,
+            quizes_index: 3,
+            owner,
+        };
+        let show = Show::deserialize_versioned(&show.try_to_vec().unwrap()).unwrap();
+        assert_eq!(show.version, StateVersion::V2);
+        assert_eq!(show.lock_time, 60);
+        assert_eq!(show.quizes_index, 3);
+        assert_eq!(show.owner, owner);
+        assert_eq!(show.pending_owner, Pubkey::default());
+
+        let user_wallet = Pubkey::new_unique();
+        let mut quiz = QuizV1 {
+            version: StateVersion::V1,
+            locked_until: 42,
+            index: 7,
+            amount: 1000,
+            ..<_>::default()
+        };
+        quiz.winners[0].user_wallet = user_wallet;
+        quiz.winners[0].points = 10;
+        let quiz = Quiz::deserialize_versioned(&quiz.try_to_vec().unwrap()).unwrap();
+        assert_eq!(quiz.version, StateVersion::V2);
+        assert_eq!(quiz.winners.len(), 1);
+        assert_eq!(quiz.winners[0].user_wallet, user_wallet);
+        assert_eq!(quiz.winners[0].points, 10);
+        assert_eq!(quiz.locked_until, 42);
+        assert_eq!(quiz.index, 7);
+        assert_eq!(quiz.amount, 1000);
     }
 }
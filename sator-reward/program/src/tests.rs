@@ -1,15 +1,19 @@
 use crate::tests_helpers::*;
 use crate::{
+    errors::Error,
     instruction::InitializeShowInput,
-    instruction::{InitializeQuizInput, InitializeViewerInput, WinnerInput},
+    instruction::{InitializeQuizInput, InitializeViewerInput, ProposeOwnerInput, WinnerInput},
     program_id,
-    state::{Quiz, Show, Viewer},
+    state::{Quiz, QuizV1, Show, ShowV1, Viewer},
     tests_helpers::*,
     transactions::{self, initialize_quiz, initialize_show, initialize_viewer, warp_seconds},
 };
-use borsh::BorshDeserialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use sator_sdk::program::PubkeyPatterns;
+use sator_sdk::state::StateVersion;
 use solana_program::native_token::sol_to_lamports;
+use solana_program::program_error::ProgramError;
+use solana_program::rent::Rent;
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
@@ -21,7 +25,7 @@ use solana_sdk::{
 };
 use std::mem;
 
-use sator_sdk_test::spl_transactions;
+use sator_sdk_test::{banks_client::assert_program_error, spl_transactions};
 
 use crate::processor::process_instruction;
 
@@ -59,6 +63,8 @@ async fn flow() {
     );
 
     let mint = Keypair::new();
+    let user_token_account =
+        add_associated_token_account(&mut program_test, &user_wallet.pubkey(), &mint.pubkey());
     let mut client = program_test.start_with_context().await;
 
     let transaction = spl_transactions::create_initialize_mint(
@@ -181,33 +187,434 @@ async fn flow() {
 
     warp_seconds(&mut client, 3 * hour).await;
 
-    let (transaction, user_token_account) = spl_transactions::create_token_account(
-        10000000,
+    let (quiz_pubkey, _) =
+        Pubkey::create_with_seed_index(&show_authority_pubkey, "Show::quizes", 0, &program_id())
+            .unwrap();
+
+    let transaction = transactions::claim(
+        &show_owner,
+        &show.pubkey(),
+        &user_wallet.pubkey(),
+        &user_token_account.pubkey(),
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let account =
+        get_token_account_state(&mut client.banks_client, &user_token_account.pubkey()).await;
+    assert_eq!(account.amount, 666);
+}
+
+#[tokio::test]
+async fn transfer_ownership() {
+    let mut program_test = new_program_test();
+
+    let show_owner = Keypair::new();
+    let new_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+
+    for wallet in [&show_owner, &new_owner, &user_wallet] {
+        program_test.add_account(
+            wallet.pubkey(),
+            Account {
+                lamports: u64::MAX / 32,
+                ..<_>::default()
+            },
+        );
+    }
+
+    let mint = Keypair::new();
+    let mut client = program_test.start_with_context().await;
+
+    let transaction = spl_transactions::create_initialize_mint(
+        &show_owner,
+        &mint,
+        &show_owner.pubkey(),
+        sol_to_lamports(10.),
+        2,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, show) = initialize_show(
+        &show_owner,
         &mint.pubkey(),
+        InitializeShowInput {
+            reward_lock_time: 60 * 60,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::propose_owner(
         &user_wallet,
-        &user_wallet,
+        &show,
+        ProposeOwnerInput {
+            pending_owner: user_wallet.pubkey(),
+        },
         client.last_blockhash,
     );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        ProgramError::IllegalOwner,
+    );
+
+    let transaction = transactions::propose_owner(
+        &show_owner,
+        &show,
+        ProposeOwnerInput {
+            pending_owner: new_owner.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let show_state = client
+        .banks_client
+        .get_account_data_with_borsh::<Show>(show)
+        .await
+        .unwrap();
+    assert_eq!(show_state.owner, show_owner.pubkey());
+    assert_eq!(show_state.pending_owner, new_owner.pubkey());
+
+    let transaction = transactions::accept_owner(&user_wallet, &show, client.last_blockhash);
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::OnlyPendingOwnerCanAcceptOwnership,
+    );
+
+    let transaction = transactions::accept_owner(&new_owner, &show, client.last_blockhash);
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let show_state = client
+        .banks_client
+        .get_account_data_with_borsh::<Show>(show)
+        .await
+        .unwrap();
+    assert_eq!(show_state.owner, new_owner.pubkey());
+    assert_eq!(show_state.pending_owner, Pubkey::default());
+
+    let transaction = initialize_viewer(
+        &show_owner,
+        &show,
+        InitializeViewerInput {
+            user: user_wallet.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        ProgramError::IllegalOwner,
+    );
+
+    let transaction = initialize_viewer(
+        &new_owner,
+        &show,
+        InitializeViewerInput {
+            user: user_wallet.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn migrate_v1_show() {
+    let mut program_test = new_program_test();
+
+    let fee_payer = Keypair::new();
+    let show_owner = Keypair::new();
+    let new_owner = Keypair::new();
+    let show = Keypair::new();
+    for wallet in [&fee_payer, &show_owner, &new_owner] {
+        program_test.add_account(
+            wallet.pubkey(),
+            Account {
+                lamports: u64::MAX / 32,
+                ..<_>::default()
+            },
+        );
+    }
+    let show_state = ShowV1 {
+        version: StateVersion::V1,
+        lock_time: 60,
+        quizes_index: 2,
+        owner: show_owner.pubkey(),
+    };
+    program_test.add_account(
+        show.pubkey(),
+        Account {
+            lamports: Rent::default().minimum_balance(ShowV1::LEN),
+            data: show_state.try_to_vec().unwrap(),
+            owner: program_id(),
+            ..<_>::default()
+        },
+    );
+    let mut client = program_test.start_with_context().await;
 
+    let transaction =
+        transactions::migrate(&fee_payer, &show.pubkey(), vec![], client.last_blockhash);
     client
         .banks_client
         .process_transaction(transaction)
         .await
         .unwrap();
 
+    let show_account = get_account(&mut client.banks_client, &show.pubkey()).await;
+    assert_eq!(show_account.data.len(), Show::LEN);
+    let show_state: Show = client
+        .banks_client
+        .get_account_data_with_borsh(show.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(show_state.version, StateVersion::V2);
+    assert_eq!(show_state.lock_time, 60);
+    assert_eq!(show_state.quizes_index, 2);
+    assert_eq!(show_state.owner, show_owner.pubkey());
+    assert_eq!(show_state.pending_owner, Pubkey::default());
+
+    // migrated show is handed over as any new one
+    let transaction = transactions::propose_owner(
+        &show_owner,
+        &show.pubkey(),
+        ProposeOwnerInput {
+            pending_owner: new_owner.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let transaction =
+        transactions::accept_owner(&new_owner, &show.pubkey(), client.last_blockhash);
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let show_state: Show = client
+        .banks_client
+        .get_account_data_with_borsh(show.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(show_state.owner, new_owner.pubkey());
+}
+
+/// Starts program with show funded by 1000 tokens and `winners` registered as viewers.
+/// Winners have associated token accounts for show mint. Returns viewers in order of `winners`.
+async fn start_with_show(
+    mut program_test: ProgramTest,
+    show_owner: &Keypair,
+    winners: &[&Keypair],
+    reward_lock_time: i64,
+) -> (ProgramTestContext, Pubkey, Keypair, Vec<Pubkey>) {
+    for wallet in [&[show_owner], winners].concat() {
+        program_test.add_account(
+            wallet.pubkey(),
+            Account {
+                lamports: u64::MAX / 32,
+                ..<_>::default()
+            },
+        );
+    }
+
+    let mint = Keypair::new();
+    for winner in winners {
+        add_associated_token_account(&mut program_test, &winner.pubkey(), &mint.pubkey());
+    }
+    let mut client = program_test.start_with_context().await;
+
+    let transaction = spl_transactions::create_initialize_mint(
+        show_owner,
+        &mint,
+        &show_owner.pubkey(),
+        sol_to_lamports(10.),
+        2,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, show) = initialize_show(
+        show_owner,
+        &mint.pubkey(),
+        InitializeShowInput { reward_lock_time },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), &program_id());
+    let token_account = Pubkey::create_with_seed(
+        &show_authority_pubkey,
+        Show::TOKEN_ACCOUNT,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let transaction = spl_transactions::mint_to(
+        show_owner,
+        &mint.pubkey(),
+        &token_account,
+        show_owner,
+        1000,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let mut viewers = vec![];
+    for winner in winners {
+        let transaction = initialize_viewer(
+            show_owner,
+            &show.pubkey(),
+            InitializeViewerInput {
+                user: winner.pubkey(),
+            },
+            client.last_blockhash,
+        );
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+        let (viewer_pubkey, _) = Pubkey::create_with_seed_for_pubkey(
+            &show_authority_pubkey,
+            &winner.pubkey(),
+            &program_id(),
+        )
+        .unwrap();
+        viewers.push(viewer_pubkey);
+    }
+    (client, show, mint, viewers)
+}
+
+/// Same as [start_with_show] with quiz `0` won by `winners` with equal points
+async fn start_with_quiz(
+    program_test: ProgramTest,
+    show_owner: &Keypair,
+    winners: &[&Keypair],
+    quiz_amount: u64,
+    reward_lock_time: i64,
+) -> (ProgramTestContext, Pubkey, Keypair, Pubkey) {
+    let (mut client, show, mint, viewers) =
+        start_with_show(program_test, show_owner, winners, reward_lock_time).await;
+    let transaction = initialize_quiz(
+        show_owner,
+        &show.pubkey(),
+        0,
+        InitializeQuizInput {
+            winners: winners
+                .iter()
+                .map(|x| WinnerInput {
+                    points: 10,
+                    owner: x.pubkey(),
+                })
+                .collect(),
+            amount: quiz_amount,
+        },
+        viewers,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), &program_id());
     let (quiz_pubkey, _) =
-        Pubkey::create_with_seed_index(&show_authority_pubkey, "Show::quizes", 0, &program_id())
+        Pubkey::create_with_seed_index(&show_authority_pubkey, Show::QUIZES, 0, &program_id())
             .unwrap();
+    (client, show, mint, quiz_pubkey)
+}
 
-    let transaction = transactions::claim(
+#[tokio::test]
+async fn winner_claims_to_own_choice() {
+    let show_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+    let other_wallet = Keypair::new();
+    let (mut client, show, mint, quiz_pubkey) = start_with_quiz(
+        new_program_test(),
         &show_owner,
-        &show.pubkey(),
+        &[&user_wallet, &other_wallet],
+        600,
+        60 * 60,
+    )
+    .await;
+    warp_seconds(&mut client, 2 * 60 * 60).await;
+
+    let (transaction, user_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &mint.pubkey(),
+        &Keypair::new(),
+        &user_wallet,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::claim(
+        &other_wallet,
+        &show,
         &user_wallet.pubkey(),
         &user_token_account.pubkey(),
         vec![quiz_pubkey],
         client.last_blockhash,
     );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::ClaimOnlyToWinnerAssociatedTokenAddress,
+    );
 
+    let transaction = transactions::claim_by_winner(
+        &user_wallet,
+        &show,
+        &user_token_account.pubkey(),
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
     client
         .banks_client
         .process_transaction(transaction)
@@ -216,5 +623,619 @@ async fn flow() {
 
     let account =
         get_token_account_state(&mut client.banks_client, &user_token_account.pubkey()).await;
-    assert_eq!(account.amount, 666);
+    assert_eq!(account.amount, 300);
+
+    let quiz_state = client
+        .banks_client
+        .get_account_data_with_borsh::<Quiz>(quiz_pubkey)
+        .await
+        .unwrap();
+    assert!(quiz_state.winners[0].claimed);
+    assert!(!quiz_state.winners[1].claimed);
+}
+
+#[tokio::test]
+async fn anybody_claims_to_winner_associated_token_account() {
+    let show_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+    let cranker = Keypair::new();
+    let (mut client, show, mint, quiz_pubkey) = start_with_quiz(
+        new_program_test(),
+        &show_owner,
+        &[&user_wallet, &cranker],
+        600,
+        60 * 60,
+    )
+    .await;
+    warp_seconds(&mut client, 2 * 60 * 60).await;
+
+    let cranker_token_account = spl_associated_token_account::get_associated_token_address(
+        &cranker.pubkey(),
+        &mint.pubkey(),
+    );
+    let user_token_account = spl_associated_token_account::get_associated_token_address(
+        &user_wallet.pubkey(),
+        &mint.pubkey(),
+    );
+
+    let transaction = transactions::claim(
+        &show_owner,
+        &show,
+        &user_wallet.pubkey(),
+        &cranker_token_account,
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::ClaimOnlyToWinnerAssociatedTokenAddress,
+    );
+
+    let transaction = transactions::claim(
+        &cranker,
+        &show,
+        &user_wallet.pubkey(),
+        &user_token_account,
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let account = get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(account.amount, 300);
+    let account = get_token_account_state(&mut client.banks_client, &cranker_token_account).await;
+    assert_eq!(account.amount, 0);
+}
+
+#[tokio::test]
+async fn claim_after_lock_time() {
+    let show_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+    let (mut client, show, mint, quiz_pubkey) = start_with_quiz(
+        new_program_test(),
+        &show_owner,
+        &[&user_wallet],
+        600,
+        60 * 60,
+    )
+    .await;
+    let user_token_account = spl_associated_token_account::get_associated_token_address(
+        &user_wallet.pubkey(),
+        &mint.pubkey(),
+    );
+
+    let transaction = transactions::claim_by_winner(
+        &user_wallet,
+        &show,
+        &user_token_account,
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::ClaimQuizIsLocked,
+    );
+
+    warp_seconds(&mut client, 15 * 60).await;
+    let transaction = transactions::claim(
+        &show_owner,
+        &show,
+        &user_wallet.pubkey(),
+        &user_token_account,
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::ClaimQuizIsLocked,
+    );
+
+    warp_seconds(&mut client, 60 * 60).await;
+    let transaction = transactions::claim(
+        &show_owner,
+        &show,
+        &user_wallet.pubkey(),
+        &user_token_account,
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let account = get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(account.amount, 600);
+}
+
+#[tokio::test]
+async fn initialize_quiz_only_for_viewers() {
+    let show_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+    let stranger = Keypair::new();
+    let (mut client, show, _, _) = start_with_quiz(
+        new_program_test(),
+        &show_owner,
+        &[&user_wallet],
+        600,
+        60 * 60,
+    )
+    .await;
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), &program_id());
+    let (viewer_pubkey, _) = Pubkey::create_with_seed_for_pubkey(
+        &show_authority_pubkey,
+        &user_wallet.pubkey(),
+        &program_id(),
+    )
+    .unwrap();
+    let winner = |wallet: &Keypair| WinnerInput {
+        points: 10,
+        owner: wallet.pubkey(),
+    };
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        1,
+        InitializeQuizInput {
+            winners: vec![winner(&stranger)],
+            amount: 100,
+        },
+        vec![viewer_pubkey],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::InitializeQuizWinnerIsNotInList,
+    );
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        1,
+        InitializeQuizInput {
+            winners: vec![winner(&user_wallet)],
+            amount: 100,
+        },
+        vec![],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::InitializeQuizViewersMustMatchWinners,
+    );
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        1,
+        InitializeQuizInput {
+            winners: vec![winner(&user_wallet)],
+            amount: 100,
+        },
+        vec![viewer_pubkey],
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let show_state = client
+        .banks_client
+        .get_account_data_with_borsh::<Show>(show)
+        .await
+        .unwrap();
+    assert_eq!(show_state.quizes_index, 2);
+}
+
+#[tokio::test]
+async fn initialize_quiz_only_with_points() {
+    let show_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+    let (mut client, show, _, viewers) =
+        start_with_show(new_program_test(), &show_owner, &[&user_wallet], 60 * 60).await;
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        0,
+        InitializeQuizInput {
+            winners: vec![],
+            amount: 100,
+        },
+        vec![],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::InitializeQuizMustHaveWinners,
+    );
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        0,
+        InitializeQuizInput {
+            winners: vec![WinnerInput {
+                points: 0,
+                owner: user_wallet.pubkey(),
+            }],
+            amount: 100,
+        },
+        viewers,
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::InitializeQuizPointsMustNotBeZero,
+    );
+}
+
+#[tokio::test]
+async fn many_winners_paged() {
+    let show_owner = Keypair::new();
+    let winners: Vec<_> = (0..25).map(|_| Keypair::new()).collect();
+    let winners: Vec<_> = winners.iter().collect();
+    let (mut client, show, mint, viewers) =
+        start_with_show(new_program_test(), &show_owner, &winners, 60 * 60).await;
+    let input = InitializeQuizInput {
+        winners: winners
+            .iter()
+            .map(|x| WinnerInput {
+                points: 10,
+                owner: x.pubkey(),
+            })
+            .collect(),
+        amount: 1000,
+    };
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        0,
+        InitializeQuizInput {
+            winners: input.winners[..=Quiz::MAX_WINNERS].to_vec(),
+            amount: 1000,
+        },
+        viewers[..=Quiz::MAX_WINNERS].to_vec(),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::TooManyWinners,
+    );
+
+    assert_eq!(
+        crate::instruction::initialize_quiz_pages(
+            &show_owner.pubkey(),
+            &show,
+            0,
+            viewers[1..].to_vec(),
+            input.clone(),
+        )
+        .unwrap_err(),
+        Error::InitializeQuizViewersMustMatchWinners.into()
+    );
+
+    let transactions = transactions::initialize_quiz_pages(
+        &show_owner,
+        &show,
+        0,
+        input,
+        viewers,
+        client.last_blockhash,
+    );
+    assert_eq!(transactions.len(), 3);
+    for transaction in transactions {
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), &program_id());
+    let quizes: Vec<_> = (0..3)
+        .map(|index| {
+            Pubkey::create_with_seed_index(
+                &show_authority_pubkey,
+                Show::QUIZES,
+                index,
+                &program_id(),
+            )
+            .unwrap()
+            .0
+        })
+        .collect();
+    for (quiz, (winners, amount)) in quizes.iter().zip([(10, 400), (10, 400), (5, 200)]) {
+        let quiz_account = get_account(&mut client.banks_client, quiz).await;
+        assert_eq!(quiz_account.data.len(), Quiz::len(winners));
+        let quiz_state = client
+            .banks_client
+            .get_account_data_with_borsh::<Quiz>(*quiz)
+            .await
+            .unwrap();
+        assert_eq!(quiz_state.winners.len(), winners);
+        assert_eq!(quiz_state.amount, amount);
+    }
+
+    warp_seconds(&mut client, 2 * 60 * 60).await;
+    for winner in [winners[0], winners[24]] {
+        let user_token_account = spl_associated_token_account::get_associated_token_address(
+            &winner.pubkey(),
+            &mint.pubkey(),
+        );
+        let transaction = transactions::claim(
+            &show_owner,
+            &show,
+            &winner.pubkey(),
+            &user_token_account,
+            quizes.clone(),
+            client.last_blockhash,
+        );
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+        let account = get_token_account_state(&mut client.banks_client, &user_token_account).await;
+        assert_eq!(account.amount, 40);
+    }
+}
+
+#[tokio::test]
+async fn paged_quiz_shares_by_points() {
+    let show_owner = Keypair::new();
+    let winners: Vec<_> = (0..Quiz::MAX_WINNERS + 2).map(|_| Keypair::new()).collect();
+    let winners: Vec<_> = winners.iter().collect();
+    let (mut client, show, mint, viewers) =
+        start_with_show(new_program_test(), &show_owner, &winners, 60 * 60).await;
+    // first page has 10 points in total and second 90
+    let points = |index: usize| if index < Quiz::MAX_WINNERS { 1 } else { 45 };
+    let input = InitializeQuizInput {
+        winners: winners
+            .iter()
+            .enumerate()
+            .map(|(index, x)| WinnerInput {
+                points: points(index),
+                owner: x.pubkey(),
+            })
+            .collect(),
+        amount: 1000,
+    };
+
+    // pages are independent quizzes, so each gets part of amount by its points
+    let transactions = transactions::initialize_quiz_pages(
+        &show_owner,
+        &show,
+        0,
+        input,
+        viewers,
+        client.last_blockhash,
+    );
+    assert_eq!(transactions.len(), 2);
+    for transaction in transactions {
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), &program_id());
+    let quizes: Vec<_> = (0..2)
+        .map(|index| {
+            Pubkey::create_with_seed_index(
+                &show_authority_pubkey,
+                Show::QUIZES,
+                index,
+                &program_id(),
+            )
+            .unwrap()
+            .0
+        })
+        .collect();
+    for (quiz, amount) in quizes.iter().zip([100, 900]) {
+        let quiz_state = client
+            .banks_client
+            .get_account_data_with_borsh::<Quiz>(*quiz)
+            .await
+            .unwrap();
+        assert_eq!(quiz_state.amount, amount);
+    }
+
+    warp_seconds(&mut client, 2 * 60 * 60).await;
+    for index in [0, Quiz::MAX_WINNERS + 1] {
+        let winner = winners[index];
+        let user_token_account = spl_associated_token_account::get_associated_token_address(
+            &winner.pubkey(),
+            &mint.pubkey(),
+        );
+        let transaction = transactions::claim(
+            &show_owner,
+            &show,
+            &winner.pubkey(),
+            &user_token_account,
+            quizes.clone(),
+            client.last_blockhash,
+        );
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+        let account = get_token_account_state(&mut client.banks_client, &user_token_account).await;
+        // same share as from single quiz of all winners
+        assert_eq!(account.amount, 1000 * points(index) as u64 / 100);
+    }
+}
+
+#[tokio::test]
+async fn initialize_quiz_winners_unique() {
+    let show_owner = Keypair::new();
+    let user_wallet = Keypair::new();
+    let (mut client, show, _, viewers) =
+        start_with_show(new_program_test(), &show_owner, &[&user_wallet], 60 * 60).await;
+    let input = InitializeQuizInput {
+        winners: vec![
+            WinnerInput {
+                points: 10,
+                owner: user_wallet.pubkey(),
+            };
+            2
+        ],
+        amount: 100,
+    };
+    let viewers = vec![viewers[0], viewers[0]];
+
+    assert_eq!(
+        crate::instruction::initialize_quiz_pages(
+            &show_owner.pubkey(),
+            &show,
+            0,
+            viewers.clone(),
+            input.clone(),
+        )
+        .unwrap_err(),
+        Error::InitializeQuizWinnersMustBeUnique.into()
+    );
+
+    let transaction = initialize_quiz(
+        &show_owner,
+        &show,
+        0,
+        input,
+        viewers,
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::InitializeQuizWinnersMustBeUnique,
+    );
+}
+
+#[tokio::test]
+async fn migrate_v1_quiz() {
+    let mut program_test = new_program_test();
+
+    let fee_payer = Keypair::new();
+    let user_wallet = Keypair::new();
+    let show = Keypair::new();
+    for payer in [&fee_payer, &user_wallet] {
+        program_test.add_account(
+            payer.pubkey(),
+            Account {
+                lamports: u64::MAX / 32,
+                ..<_>::default()
+            },
+        );
+    }
+    let show_state = Show {
+        version: StateVersion::V2,
+        lock_time: 60,
+        quizes_index: 1,
+        owner: Pubkey::new_unique(),
+        pending_owner: Pubkey::default(),
+    };
+    program_test.add_account(
+        show.pubkey(),
+        Account {
+            lamports: Rent::default().minimum_balance(Show::LEN),
+            data: show_state.try_to_vec().unwrap(),
+            owner: program_id(),
+            ..<_>::default()
+        },
+    );
+    let mut quiz_state = QuizV1 {
+        version: StateVersion::V1,
+        locked_until: 42,
+        index: 0,
+        amount: 1000,
+        ..<_>::default()
+    };
+    quiz_state.winners[0].user_wallet = user_wallet.pubkey();
+    quiz_state.winners[0].points = 10;
+    let (show_authority_pubkey, _) =
+        Pubkey::find_program_address_for_pubkey(&show.pubkey(), &program_id());
+    let (quiz_pubkey, _) =
+        Pubkey::create_with_seed_index(&show_authority_pubkey, Show::QUIZES, 0, &program_id())
+            .unwrap();
+    program_test.add_account(
+        quiz_pubkey,
+        Account {
+            lamports: Rent::default().minimum_balance(QuizV1::LEN),
+            data: quiz_state.try_to_vec().unwrap(),
+            owner: program_id(),
+            ..<_>::default()
+        },
+    );
+    let other_quiz_pubkey = Pubkey::new_unique();
+    program_test.add_account(
+        other_quiz_pubkey,
+        Account {
+            lamports: Rent::default().minimum_balance(QuizV1::LEN),
+            data: quiz_state.try_to_vec().unwrap(),
+            owner: program_id(),
+            ..<_>::default()
+        },
+    );
+    let mut client = program_test.start_with_context().await;
+
+    let transaction = transactions::claim_by_winner(
+        &user_wallet,
+        &show.pubkey(),
+        &Pubkey::new_unique(),
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        Error::ClaimQuizMustBeMigrated,
+    );
+
+    let transaction = transactions::migrate(
+        &fee_payer,
+        &show.pubkey(),
+        vec![other_quiz_pubkey],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        ProgramError::InvalidSeeds,
+    );
+
+    let transaction = transactions::migrate(
+        &fee_payer,
+        &show.pubkey(),
+        vec![quiz_pubkey],
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let quiz_account = get_account(&mut client.banks_client, &quiz_pubkey).await;
+    assert_eq!(quiz_account.data.len(), Quiz::len(1));
+    let quiz_state: Quiz = client
+        .banks_client
+        .get_account_data_with_borsh(quiz_pubkey)
+        .await
+        .unwrap();
+    assert_eq!(quiz_state.version, StateVersion::V2);
+    assert_eq!(quiz_state.winners.len(), 1);
+    assert_eq!(quiz_state.winners[0].user_wallet, user_wallet.pubkey());
+    assert_eq!(quiz_state.locked_until, 42);
+    assert_eq!(quiz_state.amount, 1000);
 }
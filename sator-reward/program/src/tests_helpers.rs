@@ -1,5 +1,5 @@
-use solana_program::{program_pack::Pack, pubkey::Pubkey};
-use solana_program_test::{BanksClient, ProgramTestContext};
+use solana_program::{program_pack::Pack, pubkey::Pubkey, rent::Rent};
+use solana_program_test::{BanksClient, ProgramTest, ProgramTestContext};
 use solana_sdk::account::Account;
 
 use sator_sdk::program::PubkeyPatterns;
@@ -19,3 +19,30 @@ pub async fn get_account(client: &mut BanksClient, pubkey: &Pubkey) -> Account {
         .expect("account not found")
         .expect("account empty")
 }
+
+/// Adds initialized associated token account of `wallet` for `mint`, so that no associated token program needed
+pub fn add_associated_token_account(
+    program_test: &mut ProgramTest,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+) -> Pubkey {
+    let address = spl_associated_token_account::get_associated_token_address(wallet, mint);
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: *mint,
+        owner: *wallet,
+        state: spl_token::state::AccountState::Initialized,
+        ..<_>::default()
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(spl_token::state::Account::LEN),
+            data,
+            owner: spl_token::id(),
+            ..<_>::default()
+        },
+    );
+    address
+}
@@ -15,6 +15,7 @@ use std::mem;
 use crate::instruction::InitializeQuizInput;
 use crate::instruction::InitializeShowInput;
 use crate::instruction::InitializeViewerInput;
+use crate::instruction::ProposeOwnerInput;
 
 pub fn initialize_show(
     owner: &Keypair,
@@ -74,9 +75,29 @@ pub fn initialize_quiz(
     transaction
 }
 
-pub fn claim(
+pub fn initialize_quiz_pages(
     owner: &Keypair,
     show: &Pubkey,
+    index: u16,
+    input: InitializeQuizInput,
+    winners: Vec<Pubkey>,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Vec<Transaction> {
+    crate::instruction::initialize_quiz_pages(&owner.pubkey(), show, index, winners, input)
+        .expect("could create derived keys")
+        .into_iter()
+        .map(|instruction| {
+            let mut transaction =
+                Transaction::new_with_payer(&[instruction], Some(&owner.pubkey()));
+            transaction.sign(&[owner], recent_blockhash);
+            transaction
+        })
+        .collect()
+}
+
+pub fn claim(
+    fee_payer: &Keypair,
+    show: &Pubkey,
     winner: &Pubkey,
     user_token_account: &TokenAccountPubkey,
     quizes: Vec<Pubkey>,
@@ -84,19 +105,84 @@ pub fn claim(
 ) -> Transaction {
     let mut transaction = Transaction::new_with_payer(
         &[crate::instruction::claim(
-            &owner.pubkey(),
+            &fee_payer.pubkey(),
             &show.pubkey(),
             winner,
             user_token_account,
             quizes,
         )
         .expect("could create derived keys")],
+        Some(&fee_payer.pubkey()),
+    );
+    transaction.sign(&[fee_payer], recent_blockhash);
+    transaction
+}
+
+pub fn claim_by_winner(
+    user_wallet_winner: &Keypair,
+    show: &Pubkey,
+    user_token_account: &TokenAccountPubkey,
+    quizes: Vec<Pubkey>,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let mut transaction = Transaction::new_with_payer(
+        &[crate::instruction::claim_by_winner(
+            &user_wallet_winner.pubkey(),
+            show,
+            user_token_account,
+            quizes,
+        )
+        .expect("could create derived keys")],
+        Some(&user_wallet_winner.pubkey()),
+    );
+    transaction.sign(&[user_wallet_winner], recent_blockhash);
+    transaction
+}
+
+pub fn propose_owner(
+    owner: &Keypair,
+    show: &Pubkey,
+    input: ProposeOwnerInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let mut transaction = Transaction::new_with_payer(
+        &[crate::instruction::propose_owner(&owner.pubkey(), show, input)
+            .expect("could create instruction")],
         Some(&owner.pubkey()),
     );
     transaction.sign(&[owner], recent_blockhash);
     transaction
 }
 
+pub fn accept_owner(
+    pending_owner: &Keypair,
+    show: &Pubkey,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let mut transaction = Transaction::new_with_payer(
+        &[crate::instruction::accept_owner(&pending_owner.pubkey(), show)
+            .expect("could create instruction")],
+        Some(&pending_owner.pubkey()),
+    );
+    transaction.sign(&[pending_owner], recent_blockhash);
+    transaction
+}
+
+pub fn migrate(
+    fee_payer: &Keypair,
+    show: &Pubkey,
+    quizes: Vec<Pubkey>,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let mut transaction = Transaction::new_with_payer(
+        &[crate::instruction::migrate(&fee_payer.pubkey(), show, quizes)
+            .expect("could create instruction")],
+        Some(&fee_payer.pubkey()),
+    );
+    transaction.sign(&[fee_payer], recent_blockhash);
+    transaction
+}
+
 pub fn create_system_account(
     payer: &Keypair,
     account: &Keypair,
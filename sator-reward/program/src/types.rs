@@ -13,3 +13,7 @@ pub struct Winner {
     pub points: u32,
     pub claimed: bool,
 }
+
+impl Winner {
+    pub const LEN: usize = 37;
+}
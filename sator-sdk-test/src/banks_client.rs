@@ -0,0 +1,35 @@
+use std::{convert::TryFrom, fmt::Debug};
+
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::{BanksClient, BanksClientError};
+use solana_sdk::{account::Account, transaction::TransactionError};
+
+pub async fn get_token_account_state(
+    client: &mut BanksClient,
+    token: &Pubkey,
+) -> spl_token::state::Account {
+    let data = get_account(client, token).await;
+    spl_token::state::Account::unpack_from_slice(&data.data[..]).unwrap()
+}
+
+pub async fn get_account(client: &mut BanksClient, pubkey: &Pubkey) -> Account {
+    client
+        .get_account(*pubkey)
+        .await
+        .expect("account not found")
+        .expect("account empty")
+}
+
+/// Asserts transaction `result` failed in instruction with `expected` program error
+pub fn assert_program_error<T: Debug>(
+    result: Result<T, BanksClientError>,
+    expected: impl Into<ProgramError>,
+) {
+    let error = match result.expect_err("transaction must fail").unwrap() {
+        TransactionError::InstructionError(_, error) => error,
+        error => panic!("transaction failed not in instruction: {:?}", error),
+    };
+    let error = ProgramError::try_from(error.clone())
+        .unwrap_or_else(|_| panic!("instruction failed not in program: {:?}", error));
+    assert_eq!(error, expected.into());
+}
@@ -1 +1,2 @@
+pub mod banks_client;
 pub mod spl_transactions;
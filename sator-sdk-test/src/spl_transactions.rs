@@ -99,3 +99,26 @@ pub fn mint_to(
     transaction.sign(&[payer, owner], recent_blockhash);
     transaction
 }
+
+pub fn approve(
+    payer: &Keypair,
+    token_account: &TokenAccountPubkey,
+    delegate: &Pubkey,
+    owner: &Keypair,
+    amount: u64,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = spl_token::instruction::approve(
+        &spl_token::id(),
+        &token_account.pubkey(),
+        delegate,
+        &owner.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap();
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    transaction.sign(&[payer, owner], recent_blockhash);
+    transaction
+}
@@ -38,16 +38,16 @@ where
 }
 
 /// helpers
-pub trait AccountWithBorsh {
+pub trait AccountWithBorsh<'a> {
     /// reads data
     fn read_data_with_borsh<T: BorshDeserialize>(&self) -> Result<T, ProgramError>;
     /// reads data mut
     fn read_data_with_borsh_mut<T: BorshDeserialize>(
         &self,
-    ) -> Result<(std::cell::RefMut<&mut [u8]>, T), ProgramError>;
+    ) -> Result<(std::cell::RefMut<'_, &'a mut [u8]>, T), ProgramError>;
 }
 
-impl<'a> AccountWithBorsh for AccountInfo<'a> {
+impl<'a> AccountWithBorsh<'a> for AccountInfo<'a> {
     fn read_data_with_borsh<T: BorshDeserialize>(&self) -> Result<T, ProgramError> {
         let data = self.try_borrow_data()?;
         Ok(T::deserialize_const(&data)?)
@@ -55,7 +55,7 @@ impl<'a> AccountWithBorsh for AccountInfo<'a> {
 
     fn read_data_with_borsh_mut<T: BorshDeserialize>(
         &self,
-    ) -> Result<(std::cell::RefMut<'a, &mut [u8]>, T), ProgramError> {
+    ) -> Result<(std::cell::RefMut<'_, &'a mut [u8]>, T), ProgramError> {
         let server_data = self.try_borrow_mut_data()?;
         let server_state = T::deserialize_const(&server_data)?;
         Ok((server_data, server_state))
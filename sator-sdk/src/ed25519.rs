@@ -0,0 +1,89 @@
+//! Messages signed off chain, verified by Ed25519 program instruction in same transaction
+use std::convert::TryInto;
+
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+
+/// count of signatures and padding
+const HEADER_LEN: usize = 2;
+/// signature, public key and message offsets with instruction indexes, all `u16`
+const OFFSETS_LEN: usize = 14;
+const PUBKEY_OFFSET: usize = HEADER_LEN + OFFSETS_LEN;
+const SIGNATURE_OFFSET: usize = PUBKEY_OFFSET + 32;
+const MESSAGE_OFFSET: usize = SIGNATURE_OFFSET + 64;
+/// instruction index meaning data is in Ed25519 instruction itself
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Creates Ed25519 program instruction verifying `signature` of `message` by `signer`
+pub fn instruction(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let mut data = Vec::with_capacity(MESSAGE_OFFSET + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for offset in [
+        SIGNATURE_OFFSET as u16,
+        CURRENT_INSTRUCTION,
+        PUBKEY_OFFSET as u16,
+        CURRENT_INSTRUCTION,
+        MESSAGE_OFFSET as u16,
+        message.len() as u16,
+        CURRENT_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&signer.to_bytes());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Signer and message of single signature Ed25519 instruction preceding current one.
+/// Runtime fails transaction if signature is invalid, so returned signer signed returned message.
+pub fn previous_signed_message(
+    sysvar_instructions: &AccountInfo,
+) -> Result<(Pubkey, Vec<u8>), ProgramError> {
+    let current = load_current_index_checked(sysvar_instructions)?;
+    let previous = current
+        .checked_sub(1)
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let instruction = load_instruction_at_checked(previous as usize, sysvar_instructions)?;
+    if instruction.program_id != ed25519_program::id() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    signed_message(&instruction.data).ok_or(ProgramError::InvalidInstructionData)
+}
+
+fn signed_message(data: &[u8]) -> Option<(Pubkey, Vec<u8>)> {
+    if *data.first()? != 1 {
+        return None;
+    }
+    let offset = |index: usize| {
+        let at = HEADER_LEN + 2 * index;
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    // data referenced from other instructions is not bound to this one
+    for index in [1, 3, 6] {
+        if offset(index)? != CURRENT_INSTRUCTION {
+            return None;
+        }
+    }
+    let pubkey_offset = offset(2)? as usize;
+    let message_offset = offset(4)? as usize;
+    let message_len = offset(5)? as usize;
+    let pubkey = data.get(pubkey_offset..pubkey_offset + 32)?;
+    let message = data.get(message_offset..message_offset + message_len)?;
+    Some((
+        Pubkey::new_from_array(pubkey.try_into().ok()?),
+        message.to_vec(),
+    ))
+}
+
@@ -5,6 +5,7 @@ use solana_program::{
     program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
+    rent::Rent,
     system_instruction,
 };
 
@@ -316,3 +317,45 @@ pub fn initialize_token_account_signed<'a>(
         &[&signers_seeds.signature()[..]],
     )
 }
+
+/// Closes token account with on chain authority, its rent goes to `destination`
+pub fn close_token_account_signed<'a>(
+    _spl_token_program: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    owner_authority: &AccountInfo<'a>,
+    signature: &ProgramPubkeySignature,
+) -> ProgramResult {
+    let instruction = spl_token::instruction::close_account(
+        &spl_token::id(),
+        account.key,
+        destination.key,
+        owner_authority.key,
+        &[],
+    )?;
+    invoke_signed(
+        &instruction,
+        &[account.clone(), destination.clone(), owner_authority.clone()],
+        &[&signature.signature()[..]],
+    )
+}
+
+/// Grows program owned `account` to `space` bytes, `payer` tops up lamports to keep it rent exempt
+pub fn realloc_rent_exempt<'a>(
+    system_program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    rent: &Rent,
+    space: usize,
+) -> ProgramResult {
+    let required_lamports = rent
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required_lamports),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(space, false)
+}
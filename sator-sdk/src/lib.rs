@@ -1,6 +1,7 @@
 //! Shared codes across various contracts.
 
 pub mod borsh;
+pub mod ed25519;
 pub mod invoke;
 pub mod program;
 pub mod spl_invoke;
@@ -23,6 +23,15 @@ pub trait PubkeyPatterns {
         owner: &ProgramPubkey,
     ) -> Result<(ProgramDerivedPubkey, String), PubkeyError>;
 
+    /// Same as [PubkeyPatterns::create_with_seed_for_pubkey] for `seed_index` 0, so existing accounts remain addressable.
+    /// Other indexes append `_` and index to seed.
+    fn create_with_seed_for_pubkey_index(
+        base: &Pubkey,
+        seed: &Pubkey,
+        seed_index: u8,
+        owner: &ProgramPubkey,
+    ) -> Result<(ProgramDerivedPubkey, String), PubkeyError>;
+
     fn create_with_seed_index(
         base: &Pubkey,
         seed: &str,
@@ -62,6 +71,22 @@ impl PubkeyPatterns for Pubkey {
         Ok((pubkey, seed))
     }
 
+    fn create_with_seed_for_pubkey_index(
+        base: &Pubkey,
+        seed: &Pubkey,
+        seed_index: u8,
+        owner: &ProgramPubkey,
+    ) -> Result<(ProgramDerivedPubkey, String), PubkeyError> {
+        if seed_index == 0 {
+            return Self::create_with_seed_for_pubkey(base, seed, owner);
+        }
+        // 28 chars of 20 bytes base58 and up to 4 chars of index fit 32 chars limit
+        let seed = seed.to_bytes();
+        let seed = format!("{}_{}", bs58::encode(&seed[..20]).into_string(), seed_index);
+        let pubkey = Pubkey::create_with_seed(base, &seed, owner)?;
+        Ok((pubkey, seed))
+    }
+
     fn create_with_seed_index(
         base: &Pubkey,
         seed: &str,
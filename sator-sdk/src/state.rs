@@ -1,13 +1,23 @@
+//! Versioned program state helpers
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, rent::Rent};
+
+use crate::{borsh::BorshSerializeConst, invoke};
 
 /// state version
 #[repr(C)]
-#[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
 pub enum StateVersion {
     /// new
     Uninitialized,
     /// version 1
     V1,
+    /// version 2
+    V2,
+    /// version 3
+    V3,
+    /// version 4
+    V4,
 }
 
 impl Default for StateVersion {
@@ -15,3 +25,50 @@ impl Default for StateVersion {
         StateVersion::Uninitialized
     }
 }
+
+impl StateVersion {
+    /// version stored as first byte of account `data`
+    pub fn read(data: &[u8]) -> Result<Self, ProgramError> {
+        Ok(Self::deserialize(&mut &data[..data.len().min(1)])?)
+    }
+}
+
+/// Account state which layout is identified by leading [StateVersion], so older layouts can be upgraded in place
+pub trait VersionedState: BorshSerialize + Sized {
+    /// version of current layout
+    const VERSION: StateVersion;
+    /// serialized size of current layout, smallest size if layout has variable size
+    const LEN: usize;
+
+    /// reads state of current or any older version from `data`, older layouts are converted to current one
+    fn deserialize_versioned(data: &[u8]) -> Result<Self, ProgramError>;
+
+    /// serialized size of `self` in current layout
+    fn packed_len(&self) -> usize {
+        Self::LEN
+    }
+}
+
+/// Upgrades `account` state to current layout of `T` in place, resizing it if needed with `payer` lamports.
+/// Returns upgraded state, `None` if it was of current version already.
+pub fn migrate_state<'a, T: VersionedState>(
+    system_program: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    rent: &Rent,
+) -> Result<Option<T>, ProgramError> {
+    let version = StateVersion::read(&account.try_borrow_data()?)?;
+    if version == T::VERSION {
+        return Ok(None);
+    }
+    if version == StateVersion::Uninitialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let state = T::deserialize_versioned(&account.try_borrow_data()?)?;
+    let len = state.packed_len();
+    if account.data_len() != len {
+        invoke::realloc_rent_exempt(system_program, payer, account, rent, len)?;
+    }
+    state.serialize_const(&mut *account.try_borrow_mut_data()?)?;
+    Ok(Some(state))
+}
@@ -64,6 +64,7 @@ fn main() {
                 StakeInput {
                     duration: 100500,
                     amount: 42,
+                    position: 0,
                 },
             )
             .unwrap()
@@ -181,20 +182,26 @@ fn main() {
                         Rank {
                             minimal_staking_time: 0,
                             amount: 100,
+                            reward_multiplier: 0,
                         },
                         Rank {
                             minimal_staking_time: 30 * 60,
                             amount: 200,
+                            reward_multiplier: 0,
                         },
                         Rank {
                             minimal_staking_time: 60 * 60,
                             amount: 300,
+                            reward_multiplier: 0,
                         },
                         Rank {
                             minimal_staking_time: 2 * 60 * 60,
                             amount: 500,
+                            reward_multiplier: 0,
                         },
                     ],
+                    kyc_required: false,
+                    reward_mint: Pubkey::default(),
                 },
             )
             .unwrap()],
@@ -225,6 +232,7 @@ fn main() {
                     StakeInput {
                         duration: 100500,
                         amount: 42,
+                        position: 0,
                     },
                 )
                 .unwrap()
@@ -19,6 +19,40 @@ pub enum Error {
     StakeForViewerMustBeSignedByUserWalletOrPoolAdmin,
     AdminCanUnstakeOnlyToUserWalletAssosiatedTokenAddress,
     StakePoolTokenAccountMustBeDerivedFromPool,
+    UnstakeRewardPoolUnderfunded,
+    RanksMustBeOrderedByTimeAndAmount,
+    OnlyPendingOwnerCanAcceptOwnership,
+    StakeViewerMustBeKycVerified,
+    UnstakeAmountExceedsStake,
+    EarlyUnstakeIsNotEnabled,
+    EarlyUnstakeCanBeDoneOnlyBeforeStakeTimeLapsed,
+    EarlyUnstakePenaltyMustNotExceedWholeAmount,
+    EarlyUnstakeTreasuryMustHoldPoolMint,
+    EarlyUnstakeTreasuryMustBeSetOnPool,
+    GetRankPositionsMustBeOfSingleWallet,
+    GetRankPositionsMustBeUnique,
+    StakeOverflow,
+    TopUpRequiresActiveLock,
+    ExtendLockCanBeDoneOnlyBeforeStakeTimeLapsed,
+    StakePoolIsPaused,
+    RewardReserveMustBeDerivedFromPool,
+    RewardReserveMustHoldPoolMint,
+    WithdrawExceedsUnallocatedRewards,
+    StakeAmountBelowMinimum,
+    StakeExceedsWalletCap,
+    StakeExceedsPoolCapacity,
+    ClosePoolMustHaveNoStakes,
+    ClosePoolRewardReserveMustBeWithdrawn,
+    DelegatedStakeSourceMustBeOwnedByUserWallet,
+    DelegatedStakeMustBeApprovedToStakeAuthority,
+    IntentMustBeSignedByUserWallet,
+    IntentMustMatchAccounts,
+    IntentNonceMustMatchWallet,
+    IntentExpired,
+    ViewerWalletMustBeDerivedFromUserWallet,
+    ViewerWalletMustBeOwnedByThisContract,
+    DelegatedStakeMustUseMinimalStakingTime,
+    DelegatedStakeMustNotRestake,
 }
 
 impl Error {
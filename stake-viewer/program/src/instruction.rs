@@ -3,17 +3,68 @@ use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use sator_sdk::program::PubkeyPatterns;
 use sator_sdk::types::{
     ApproximateSeconds, MintPubkey, SignerPubkey, TokenAccountPubkey, TokenAmount, PossiblySignerPubkey,
+    ProgramPubkey,
 };
+use solana_program::clock::UnixTimestamp;
 use solana_program::instruction::AccountMeta;
 use solana_program::program_error::ProgramError;
 use solana_program::pubkey::Pubkey;
 use solana_program::{system_program, sysvar};
 
-use crate::{stake_viewer_program_id, types::*};
+use crate::{stake_viewer_program_id, state::ViewerWallet, types::*};
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
 pub struct InitializeStakePoolInput {
     pub ranks: [Rank; 4],
+    /// only viewers with [crate::state::ViewerWallet::kyc_verified] can stake
+    pub kyc_required: bool,
+    /// mint rewards are paid in, default pays rewards in staked mint
+    pub reward_mint: MintPubkey,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct UpdateRanksInput {
+    pub ranks: [Rank; 4],
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ProposeOwnerInput {
+    /// default pubkey cancels proposal
+    pub pending_owner: Pubkey,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct SetViewerKycInput {
+    pub kyc_verified: bool,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct SetEarlyUnstakePenaltyInput {
+    /// in [crate::state::ViewerStakePool::BPS] units, not more than whole amount
+    pub penalty_bps: u16,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct RewardsInput {
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct SetPoolModeInput {
+    /// blocks staking new tokens
+    pub paused: bool,
+    /// allows unstake of principal without reward regardless of lock, blocks staking new tokens
+    pub emergency: bool,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct SetStakeLimitsInput {
+    /// minimal amount of stake position after deposit
+    pub min_stake: TokenAmount,
+    /// maximal amount of all stake positions of wallet, zero if unlimited
+    pub max_stake_per_wallet: TokenAmount,
+    /// maximal amount staked into pool, zero if unlimited
+    pub capacity: TokenAmount,
 }
 
 #[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
@@ -21,19 +72,106 @@ pub struct StakeInput {
     /// any of times from [crate::state::ViewerStakePool::ranks] or more
     pub duration: ApproximateSeconds,
     pub amount: TokenAmount,
+    /// index of independent position of `user_wallet` in pool, 0 for first
+    pub position: u8,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct AddToStakeInput {
+    pub amount: TokenAmount,
+    /// existing position of `user_wallet` to add to
+    pub position: u8,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ExtendLockInput {
+    /// new lock duration counted from [crate::state::ViewerStake::staked_at], not less than current
+    pub duration: ApproximateSeconds,
+    pub position: u8,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct UnstakeInput {
+    /// part of [crate::state::ViewerStake::amount] to unstake, `None` unstakes all
+    pub amount: Option<TokenAmount>,
+    /// position of `user_wallet` to unstake from
+    pub position: u8,
+}
+
+/// [Instruction::Stake] `user_wallet` signs off chain, so relayer can execute it
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct StakeIntentInput {
+    pub stake_pool: Pubkey,
+    /// token account of `user_wallet` with `stake_authority` approved as delegate
+    pub token_account_source: TokenAccountPubkey,
+    pub stake: StakeInput,
+    /// [crate::state::ViewerWallet::nonce] of `user_wallet`, 0 for new wallet
+    pub nonce: u64,
+    /// intent cannot be executed after
+    pub valid_until: UnixTimestamp,
+}
+
+/// [Instruction::Unstake] `user_wallet` signs off chain, so relayer can execute it
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct UnstakeIntentInput {
+    pub stake_pool: Pubkey,
+    pub token_account_target: TokenAccountPubkey,
+    pub reward_token_account_target: TokenAccountPubkey,
+    pub unstake: UnstakeInput,
+    /// [crate::state::ViewerWallet::nonce] of `user_wallet`
+    pub nonce: u64,
+    /// intent cannot be executed after
+    pub valid_until: UnixTimestamp,
 }
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
 pub enum Instruction {
-    ///Like: 0u8[(i64,u64),(i64,u64),(i64,u64),(i64,u64)]
+    ///Like: 0u8[(i64,u64,u64),(i64,u64,u64),(i64,u64,u64),(i64,u64,u64)]bool[u8;32]
     InitializeStakePool(InitializeStakePoolInput),
-    ///Like: 1u8(i64,u64)
+    ///Like: 1u8(i64,u64,u8)
     Stake(StakeInput),
-    //Like: 2u8
-    Unstake,
+    ///Like: 2u8(Option<u64>,u8)
+    Unstake(UnstakeInput),
+    ///Like: 3u8
+    GetRank,
+    ///Like: 4u8[(i64,u64,u64),(i64,u64,u64),(i64,u64,u64),(i64,u64,u64)]
+    UpdateRanks(UpdateRanksInput),
+    ///Like: 5u8([u8;32])
+    ProposeOwner(ProposeOwnerInput),
+    ///Like: 6u8
+    AcceptOwner,
+    ///Like: 7u8(bool)
+    SetViewerKyc(SetViewerKycInput),
+    ///Like: 8u8(u16)
+    SetEarlyUnstakePenalty(SetEarlyUnstakePenaltyInput),
+    ///Like: 9u8(Option<u64>,u8)
+    EarlyUnstake(UnstakeInput),
+    ///Like: 10u8(u64,u8)
+    AddToStake(AddToStakeInput),
+    ///Like: 11u8(i64,u8)
+    ExtendLock(ExtendLockInput),
+    ///Like: 12u8(bool,bool)
+    SetPoolMode(SetPoolModeInput),
+    ///Like: 13u8(u64)
+    FundRewards(RewardsInput),
+    ///Like: 14u8(u64)
+    WithdrawUnallocatedRewards(RewardsInput),
+    ///Like: 15u8
+    Migrate,
+    ///Like: 16u8(u64,u64,u64)
+    SetStakeLimits(SetStakeLimitsInput),
+    ///Like: 17u8
+    ClosePool,
+    ///Like: 18u8(i64,u64,u8)
+    DelegatedStake(StakeInput),
+    ///Like: 19u8[u8;32][u8;32](i64,u64,u8)u64i64
+    StakeWithIntent(StakeIntentInput),
+    ///Like: 20u8[u8;32][u8;32][u8;32](Option<u64>,u8)u64i64
+    UnstakeWithIntent(UnstakeIntentInput),
 }
 
 /// Creates [Instruction::InitializeStakePool] instruction which initializes `stake_pool` and `token_account`
+/// Ranks must be ordered by time and amount.
 ///
 /// Accounts:
 ///  * `system_program`           - *program, implicit* to create accounts
@@ -78,7 +216,7 @@ pub fn initialize_stake_pool(
 }
 
 /// Creates [Instruction::Stake] instruction which transfer `amount` from `token_account_source` to `token_account_stake_target`.
-/// If `stake_account` initialized, resets timer.
+/// If `stake_account` initialized, resets timer, reward earned so far is kept as [crate::state::ViewerStake::carried_reward].
 /// Locks amount for period of time defined in `stake_pool`.
 ///
 /// Accounts:
@@ -87,11 +225,12 @@ pub fn initialize_stake_pool(
 ///  * `clock`                         - *program, implicit*
 ///  * `spl_token`                     - *program, implicit*
 ///  * `fee_payer`                     - *signer, payer* pays for account creation
-///  * `stake_pool`                    - account of stake pool used
+///  * `stake_pool`                    - *mutable* account of stake pool used
 ///  * `stake_authority`               - *derived*  as in [Instruction::InitializeStakePool]
 ///  * `token_account_source`          - *mutable* represents user and has approval for input amount
 ///  * `token_account_stake_target`    - *derived, mutable, implicit*
-///  * `viewer_stake_account`          - *implicit, derived, mutable* from `user_wallet` and `stake_authority`
+///  * `viewer_stake_account`          - *implicit, derived, mutable* from `user_wallet`, `stake_authority` and `input.position`
+///  * `viewer_wallet`                 - *implicit, derived, mutable* [crate::state::ViewerWallet] of `user_wallet`, created if not exists
 ///  * `user_wallet`                   - *signer* something to which user has private key of
 ///  * `stake_pool_owner`              - *optional, signer* owner of stake pool can create stake for viewer, in this case `user_wallet` may not be signer  
 ///
@@ -106,6 +245,111 @@ pub fn stake(
     user_wallet: &PossiblySignerPubkey,
     stake_pool_owner: &Option<SignerPubkey>,
     input: StakeInput,
+) -> Result<(solana_program::instruction::Instruction, Pubkey), ProgramError> {
+    let position = input.position;
+    stake_instruction(
+        fee_payer,
+        stake_pool,
+        token_account_source,
+        user_wallet,
+        position,
+        Instruction::Stake(input),
+        signed_by_wallet_or_owner(user_wallet, stake_pool_owner),
+    )
+}
+
+/// Creates [Instruction::DelegatedStake] instruction which is [Instruction::Stake] of tokens `user_wallet` approved to `stake_authority`.
+/// `stake_pool_owner` triggers stake, so `user_wallet` does not sign.
+/// Unless `user_wallet` signs, stake must be locked for minimal staking time of first rank into new position,
+/// longer lock or restake of existing position is done by [Instruction::StakeWithIntent].
+///
+/// Accounts as in [Instruction::Stake], but:
+///  * `token_account_source`          - *mutable* owned by `user_wallet` with `stake_authority` delegate approved for input amount
+///  * `stake_pool_owner`              - *signer* owner of stake pool
+pub fn delegated_stake(
+    fee_payer: &SignerPubkey,
+    stake_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    user_wallet: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    input: StakeInput,
+) -> Result<(solana_program::instruction::Instruction, Pubkey), ProgramError> {
+    let position = input.position;
+    stake_instruction(
+        fee_payer,
+        stake_pool,
+        token_account_source,
+        user_wallet,
+        position,
+        Instruction::DelegatedStake(input),
+        signed_by_wallet_or_owner(user_wallet, &Some(*stake_pool_owner)),
+    )
+}
+
+/// Creates [Instruction::StakeWithIntent] instruction which is [Instruction::DelegatedStake] authorized by `user_wallet` signature of [intent_message] off chain instead of `stake_pool_owner`.
+/// Must directly follow Ed25519 program instruction verifying that signature, see [sator_sdk::ed25519::instruction], so any `fee_payer` can relay it.
+///
+/// Accounts as in [Instruction::Stake], but:
+///  * `token_account_source` - *mutable* `input.token_account_source` owned by `user_wallet` with `stake_authority` delegate approved for input amount
+///  * `user_wallet`          - signed intent off chain
+///  * `sysvar_instructions`  - *program, implicit* to read Ed25519 instruction
+pub fn stake_with_intent(
+    fee_payer: &SignerPubkey,
+    user_wallet: &Pubkey,
+    input: StakeIntentInput,
+) -> Result<(solana_program::instruction::Instruction, Pubkey), ProgramError> {
+    let stake_pool = input.stake_pool;
+    let token_account_source = input.token_account_source;
+    let position = input.stake.position;
+    stake_instruction(
+        fee_payer,
+        &stake_pool,
+        &token_account_source,
+        user_wallet,
+        position,
+        Instruction::StakeWithIntent(input),
+        signed_by_intent(user_wallet),
+    )
+}
+
+/// Message `user_wallet` signs off chain to authorize intent instruction with `data`.
+/// Binds intent to program and `stake_pool`, so it cannot be executed by other deployment or on other pool.
+pub fn intent_message(program_id: &ProgramPubkey, stake_pool: &Pubkey, data: &[u8]) -> Vec<u8> {
+    [program_id.as_ref(), stake_pool.as_ref(), data].concat()
+}
+
+/// `user_wallet` and `stake_pool_owner` accounts, only one of which signs
+fn signed_by_wallet_or_owner(
+    user_wallet: &PossiblySignerPubkey,
+    stake_pool_owner: &Option<SignerPubkey>,
+) -> Vec<AccountMeta> {
+    if stake_pool_owner.is_none() {
+        vec![AccountMeta::new_readonly(*user_wallet, true)]
+    }
+    else {
+        vec![
+            AccountMeta::new_readonly(*user_wallet, false),
+            AccountMeta::new_readonly(stake_pool_owner.unwrap(), true),
+        ]
+    }
+}
+
+/// `user_wallet` which signed intent off chain and sysvar to check it
+fn signed_by_intent(user_wallet: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*user_wallet, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ]
+}
+
+fn stake_instruction(
+    fee_payer: &SignerPubkey,
+    stake_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    user_wallet: &PossiblySignerPubkey,
+    position: u8,
+    instruction: Instruction,
+    authorization: Vec<AccountMeta>,
 ) -> Result<(solana_program::instruction::Instruction, Pubkey), ProgramError> {
     let (stake_authority, _) =
         Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
@@ -115,36 +359,33 @@ pub fn stake(
         &spl_token::id(),
     )?;
 
-    let viewer_stake_account = Pubkey::create_with_seed_for_pubkey(
+    let viewer_stake_account = Pubkey::create_with_seed_for_pubkey_index(
         &stake_authority,
         user_wallet,
+        position,
         &stake_viewer_program_id(),
     )?;
+    let (viewer_wallet, _) =
+        ViewerWallet::derive(&stake_authority, user_wallet, &stake_viewer_program_id())?;
     let mut accounts = vec![
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(*fee_payer, true),
-        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*stake_pool, false),
         AccountMeta::new_readonly(stake_authority, false),
         AccountMeta::new(*token_account_source, false),
         AccountMeta::new(token_account_stake_target, false),
         AccountMeta::new(viewer_stake_account.0, false),
+        AccountMeta::new(viewer_wallet, false),
     ];
-
-    if stake_pool_owner.is_none() {
-        accounts.push(AccountMeta::new_readonly(*user_wallet, true));
-    }
-    else {
-        accounts.push(AccountMeta::new_readonly(*user_wallet, false));
-        accounts.push(AccountMeta::new_readonly(stake_pool_owner.unwrap(), true));
-    }
+    accounts.extend(authorization);
 
     Ok((
         solana_program::instruction::Instruction::new_with_borsh(
             crate::id(),
-            &Instruction::Stake(input),
+            &instruction,
             accounts,
         ),
         viewer_stake_account.0,
@@ -152,7 +393,12 @@ pub fn stake(
 }
 
 /// Creates [Instruction::Unstake] instruction which transfer `amount` from `token_account_stake_source` to `token_account_target` if and only if now is more than [crate::state::ViewerStake::staked_until]
-/// Resets unlock.
+/// Unstakes `input.amount` or whole stake if `None`. Whole unstake closes `user_stake_account`, partial keeps rest staked.
+///
+/// Pays reward of rank reached by stake on top of unstaked amount, prorated by unstaked amount, from `reward_reserve`, fails if it has not enough tokens.
+/// No reward is paid in [crate::state::ViewerStakePool::emergency] mode.
+///
+/// Returns rank held by rest of stake as borsh `Option<u8>` via return data, `None` if closed.
 ///
 /// If `token_account_target` is associated token account of `user_wallet` 
 ///
@@ -160,19 +406,77 @@ pub fn stake(
 ///  * `sysvar_clock`               - *program, implicit* used to check lock maturity
 ///  * `spl_token`                  - *program, implicit*
 ///  * `fee_payer`                  - *mutable, payer*, in case of full unlock, will transfers sols from lock onto this account
-///  * `stake_pool`                 - state account initialized
+///  * `stake_pool`                 - *mutable* state account initialized
 ///  * `stake_authority`            - *implicit*, derived from `owner`
 ///  * `token_account_target`       - *mutable* represent user account for SAO tokens
 ///  * `token_account_stake_source` - *derived, mutable, implicit*
-///  * `user_stake_account`         - *implicit, derived, mutable* from `user_wallet` and `stake_authority`
+///  * `reward_reserve`             - *derived, mutable, implicit* as in [Instruction::FundRewards]
+///  * `reward_token_account_target` - *mutable* receives reward, holds [crate::state::ViewerStakePool::reward_mint] or same as `token_account_target` if rewards are paid in staked mint
+///  * `user_stake_account`         - *implicit, derived, mutable* from `user_wallet`, `stake_authority` and `input.position`
+///  * `viewer_wallet`              - *implicit, derived, mutable* [crate::state::ViewerWallet] of `user_wallet`, skipped if not exists
 ///  * `user_wallet`                - *signer* owner of `user_stake_account`, can transfer to any `token_account_stake_source` if signed. 
-///  * `stake_pool_owner`           - *optional, signer*, if `user_wallet` is not signer,  than only to if `token_account_target` associated token account signature of `stake_pool_owner` is enough
+///  * `stake_pool_owner`           - *optional, signer*, if `user_wallet` is not signer,  than only to if `token_account_target` and `reward_token_account_target` are associated token accounts signature of `stake_pool_owner` is enough
 pub fn unstake(
     stake_pool: &Pubkey,
     token_account_target: &TokenAccountPubkey,
+    reward_token_account_target: &TokenAccountPubkey,
     user_wallet: &PossiblySignerPubkey,
     fee_payer: &Pubkey,
     stake_pool_owner: &Option<SignerPubkey>,
+    input: UnstakeInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let position = input.position;
+    unstake_instruction(
+        stake_pool,
+        token_account_target,
+        reward_token_account_target,
+        user_wallet,
+        fee_payer,
+        position,
+        Instruction::Unstake(input),
+        signed_by_wallet_or_owner(user_wallet, stake_pool_owner),
+    )
+}
+
+/// Creates [Instruction::UnstakeWithIntent] instruction which is [Instruction::Unstake] authorized by `user_wallet` signature of [intent_message] off chain.
+/// Must directly follow Ed25519 program instruction verifying that signature, see [sator_sdk::ed25519::instruction], so any `fee_payer` can relay it.
+///
+/// Accounts as in [Instruction::Unstake], but:
+///  * `token_account_target`        - *mutable* `input.token_account_target`
+///  * `reward_token_account_target` - *mutable* `input.reward_token_account_target`
+///  * `user_wallet`                 - signed intent off chain
+///  * `sysvar_instructions`         - *program, implicit* to read Ed25519 instruction
+pub fn unstake_with_intent(
+    fee_payer: &Pubkey,
+    user_wallet: &Pubkey,
+    input: UnstakeIntentInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let stake_pool = input.stake_pool;
+    let token_account_target = input.token_account_target;
+    let reward_token_account_target = input.reward_token_account_target;
+    let position = input.unstake.position;
+    unstake_instruction(
+        &stake_pool,
+        &token_account_target,
+        &reward_token_account_target,
+        user_wallet,
+        fee_payer,
+        position,
+        Instruction::UnstakeWithIntent(input),
+        signed_by_intent(user_wallet),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn unstake_instruction(
+    stake_pool: &Pubkey,
+    token_account_target: &TokenAccountPubkey,
+    reward_token_account_target: &TokenAccountPubkey,
+    user_wallet: &PossiblySignerPubkey,
+    fee_payer: &Pubkey,
+    position: u8,
+    instruction: Instruction,
+    authorization: Vec<AccountMeta>,
 ) -> Result<solana_program::instruction::Instruction, ProgramError> {
     let (stake_authority, _) =
         Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
@@ -181,33 +485,579 @@ pub fn unstake(
         "ViewerStakePool::token_account",
         &spl_token::id(),
     )?;
-    let user_stake_account = Pubkey::create_with_seed_for_pubkey(
+    let reward_reserve = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::reward_reserve",
+        &spl_token::id(),
+    )?;
+    let user_stake_account = Pubkey::create_with_seed_for_pubkey_index(
         &stake_authority,
         user_wallet,
+        position,
         &stake_viewer_program_id(),
     )?;
+    let (viewer_wallet, _) =
+        ViewerWallet::derive(&stake_authority, user_wallet, &stake_viewer_program_id())?;
     let mut accounts = vec![
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new(*fee_payer, false),
-        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*stake_pool, false),
         AccountMeta::new_readonly(stake_authority, false),
         AccountMeta::new(*token_account_target, false),
         AccountMeta::new(token_account_stake_source, false),
+        AccountMeta::new(reward_reserve, false),
+        AccountMeta::new(*reward_token_account_target, false),
         AccountMeta::new(user_stake_account.0, false),
+        AccountMeta::new(viewer_wallet, false),
     ];
+    accounts.extend(authorization);
 
-    if stake_pool_owner.is_none() {
-        accounts.push(AccountMeta::new_readonly(*user_wallet, true));
-    }
-    else {
-        accounts.push(AccountMeta::new_readonly(*user_wallet, false));
-        accounts.push(AccountMeta::new_readonly(stake_pool_owner.unwrap(), true));
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &instruction,
+        accounts,
+    ))
+}
+
+/// Creates [Instruction::GetRank] instruction which returns index of rank held by `positions` of `user_wallet` as borsh `Option<u8>` via return data.
+/// Rank is reached if sum of amounts of positions staked for its minimal time reaches its amount.
+/// Does not change any state.
+///
+/// Accounts:
+///  * `sysvar_clock`          - *program, implicit* stake which lock lapsed holds only ranks without minimal staking time
+///  * `stake_pool`            - source of ranks
+///  * `viewer_stake_accounts` - *implicit, derived, collection* from `user_wallet`, `stake_authority` and each of `positions`
+pub fn get_rank(
+    stake_pool: &Pubkey,
+    user_wallet: &Pubkey,
+    positions: &[u8],
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let mut accounts = vec![
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*stake_pool, false),
+    ];
+    for position in positions {
+        let (viewer_stake_account, _) = Pubkey::create_with_seed_for_pubkey_index(
+            &stake_authority,
+            user_wallet,
+            *position,
+            &stake_viewer_program_id(),
+        )?;
+        accounts.push(AccountMeta::new_readonly(viewer_stake_account, false));
     }
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::GetRank,
+        accounts,
+    ))
+}
+
+/// Creates [Instruction::UpdateRanks] instruction which replaces ranks of `stake_pool`.
+/// Ranks must be ordered by time and amount.
+/// Existing stakes keep reward terms they staked under until unstake.
+///
+/// Accounts:
+///  * `stake_pool`       - *mutable* initialized stake pool
+///  * `stake_pool_owner` - *signer* owner of `stake_pool`
+pub fn update_ranks(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    input: UpdateRanksInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::UpdateRanks(input),
+        vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::SetPoolMode] instruction which sets [crate::state::ViewerStakePool::paused] and [crate::state::ViewerStakePool::emergency].
+///
+/// Accounts:
+///  * `stake_pool`       - *mutable* initialized stake pool
+///  * `stake_pool_owner` - *signer* owner of `stake_pool`
+pub fn set_pool_mode(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    input: SetPoolModeInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::SetPoolMode(input),
+        vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::SetStakeLimits] instruction which sets [crate::state::ViewerStakePool::min_stake], [crate::state::ViewerStakePool::max_stake_per_wallet] and [crate::state::ViewerStakePool::capacity].
+/// Limits are checked on deposit only, existing stakes are kept.
+///
+/// Accounts:
+///  * `stake_pool`       - *mutable* initialized stake pool
+///  * `stake_pool_owner` - *signer* owner of `stake_pool`
+pub fn set_stake_limits(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    input: SetStakeLimitsInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::SetStakeLimits(input),
+        vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::ProposeOwner] instruction which stores `input.pending_owner` on `stake_pool`.
+/// Ownership is not changed until [Instruction::AcceptOwner] signed by `input.pending_owner`.
+///
+/// Accounts:
+///  * `stake_pool`       - *mutable* initialized stake pool
+///  * `stake_pool_owner` - *signer* current owner of `stake_pool`
+pub fn propose_owner(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    input: ProposeOwnerInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::ProposeOwner(input),
+        vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::AcceptOwner] instruction which makes `pending_owner` owner of `stake_pool` and clears proposal.
+///
+/// Accounts:
+///  * `stake_pool`    - *mutable* initialized stake pool
+///  * `pending_owner` - *signer* owner proposed by [Instruction::ProposeOwner]
+pub fn accept_owner(
+    stake_pool: &Pubkey,
+    pending_owner: &SignerPubkey,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::AcceptOwner,
+        vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(*pending_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::SetViewerKyc] instruction which sets [crate::state::ViewerWallet::kyc_verified] of `user_wallet`.
+/// Creates `viewer_wallet` if viewer has not staked yet, so KYC can be done before first stake.
+/// `viewer_wallet` is never closed, so KYC is kept for all positions, including ones opened after whole unstake.
+///
+/// Accounts:
+///  * `system_program`   - *program, implicit* to create `viewer_wallet`
+///  * `sysvar_rent`      - *program, implicit* to create `viewer_wallet` which will be rent except
+///  * `fee_payer`        - *signer, payer* pays for account creation
+///  * `stake_pool`       - initialized stake pool
+///  * `stake_authority`  - *implicit, derived* as in [Instruction::InitializeStakePool]
+///  * `viewer_wallet`    - *implicit, derived, mutable* from `user_wallet` and `stake_authority`, see [crate::state::ViewerWallet::derive]
+///  * `user_wallet`      - wallet of viewer
+///  * `stake_pool_owner` - *signer* owner of `stake_pool`
+pub fn set_viewer_kyc(
+    fee_payer: &SignerPubkey,
+    stake_pool: &Pubkey,
+    user_wallet: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    input: SetViewerKycInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let (viewer_wallet, _) =
+        ViewerWallet::derive(&stake_authority, user_wallet, &stake_viewer_program_id())?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::SetViewerKyc(input),
+        vec![
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(*fee_payer, true),
+            AccountMeta::new_readonly(*stake_pool, false),
+            AccountMeta::new_readonly(stake_authority, false),
+            AccountMeta::new(viewer_wallet, false),
+            AccountMeta::new_readonly(*user_wallet, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::SetEarlyUnstakePenalty] instruction which enables [Instruction::EarlyUnstake] on `stake_pool`.
+/// Sets penalty withheld on early unstake of positions staked or restaked afterwards, and `treasury` receiving it.
+/// Existing positions keep penalty of pool when staked, see [crate::state::ViewerStake::early_unstake_penalty_bps].
+///
+/// Accounts:
+///  * `stake_pool`               - *mutable* initialized stake pool
+///  * `stake_pool_owner`         - *signer* owner of `stake_pool`
+///  * `token_account_stake_pool` - *implicit, derived* to check `treasury` mint
+///  * `treasury`                 - token account of same mint as staked to receive penalties
+pub fn set_early_unstake_penalty(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    treasury: &TokenAccountPubkey,
+    input: SetEarlyUnstakePenaltyInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let token_account_stake_pool = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::SetEarlyUnstakePenalty(input),
+        vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+            AccountMeta::new_readonly(token_account_stake_pool, false),
+            AccountMeta::new_readonly(*treasury, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::EarlyUnstake] instruction which unstakes before [crate::state::ViewerStake::staked_until].
+/// Unstakes `input.amount` or whole stake if `None`, rest stays locked under same terms. Whole unstake closes `user_stake_account`.
+///
+/// No reward is paid, [crate::state::ViewerStake::early_unstake_penalty] is transferred to `treasury` and rest to `token_account_target`.
+///
+/// Returns rank held by rest of stake as borsh `Option<u8>` via return data, `None` if closed.
+///
+/// Accounts:
+///  * `sysvar_clock`               - *program, implicit* used to check lock is not lapsed
+///  * `spl_token`                  - *program, implicit*
+///  * `fee_payer`                  - *mutable, payer*, in case of full unstake, will transfers sols from lock onto this account
+///  * `stake_pool`                 - *mutable* state account initialized with early unstake enabled
+///  * `stake_authority`            - *implicit*, derived from `stake_pool`
+///  * `token_account_target`       - *mutable* represent user account for SAO tokens
+///  * `token_account_stake_source` - *derived, mutable, implicit*
+///  * `user_stake_account`         - *implicit, derived, mutable* from `user_wallet`, `stake_authority` and `input.position`
+///  * `viewer_wallet`              - *implicit, derived, mutable* [crate::state::ViewerWallet] of `user_wallet`, skipped if not exists
+///  * `user_wallet`                - *signer* owner of `user_stake_account`
+///  * `treasury`                   - *mutable* [crate::state::ViewerStakePool::treasury]
+#[allow(clippy::too_many_arguments)]
+pub fn early_unstake(
+    stake_pool: &Pubkey,
+    token_account_target: &TokenAccountPubkey,
+    user_wallet: &SignerPubkey,
+    fee_payer: &Pubkey,
+    treasury: &TokenAccountPubkey,
+    input: UnstakeInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let token_account_stake_source = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )?;
+    let user_stake_account = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority,
+        user_wallet,
+        input.position,
+        &stake_viewer_program_id(),
+    )?;
+    let (viewer_wallet, _) =
+        ViewerWallet::derive(&stake_authority, user_wallet, &stake_viewer_program_id())?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::EarlyUnstake(input),
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*fee_payer, false),
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(stake_authority, false),
+            AccountMeta::new(*token_account_target, false),
+            AccountMeta::new(token_account_stake_source, false),
+            AccountMeta::new(user_stake_account.0, false),
+            AccountMeta::new(viewer_wallet, false),
+            AccountMeta::new_readonly(*user_wallet, true),
+            AccountMeta::new(*treasury, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::AddToStake] instruction which transfers `input.amount` from `token_account_source` into existing stake keeping its lock.
+/// Added tokens earn reward only for rest of lock, under terms of rank reached by whole amount for rest of lock.
+/// Stake must be locked at least for minimal staking time of first rank, lapsed stakes are restaked with [Instruction::Stake].
+/// Anybody can top up stake of viewer as it cannot reduce rank.
+///
+/// Returns rank held by stake as borsh `Option<u8>` via return data.
+///
+/// Accounts:
+///  * `system_program`             - *program, implicit* to create `viewer_wallet`
+///  * `sysvar_rent`                - *program, implicit* to create `viewer_wallet` which will be rent except
+///  * `sysvar_clock`               - *program, implicit*
+///  * `spl_token`                  - *program, implicit*
+///  * `fee_payer`                  - *signer, payer* pays for `viewer_wallet` creation
+///  * `stake_pool`                 - *mutable* account of stake pool used
+///  * `stake_authority`            - *implicit, derived* as in [Instruction::InitializeStakePool]
+///  * `token_account_source`       - *mutable* tokens to add
+///  * `token_account_stake_target` - *derived, mutable, implicit*
+///  * `viewer_stake_account`       - *implicit, derived, mutable* from `user_wallet`, `stake_authority` and `input.position`
+///  * `viewer_wallet`              - *implicit, derived, mutable* [crate::state::ViewerWallet] of `user_wallet`, created if not exists
+///  * `user_wallet`                - owner of `viewer_stake_account`
+///  * `source_authority`           - *signer* owner or delegate of `token_account_source`
+pub fn add_to_stake(
+    fee_payer: &SignerPubkey,
+    stake_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    user_wallet: &Pubkey,
+    source_authority: &SignerPubkey,
+    input: AddToStakeInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let token_account_stake_target = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )?;
+    let viewer_stake_account = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority,
+        user_wallet,
+        input.position,
+        &stake_viewer_program_id(),
+    )?;
+    let (viewer_wallet, _) =
+        ViewerWallet::derive(&stake_authority, user_wallet, &stake_viewer_program_id())?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::AddToStake(input),
+        vec![
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*fee_payer, true),
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(stake_authority, false),
+            AccountMeta::new(*token_account_source, false),
+            AccountMeta::new(token_account_stake_target, false),
+            AccountMeta::new(viewer_stake_account.0, false),
+            AccountMeta::new(viewer_wallet, false),
+            AccountMeta::new_readonly(*user_wallet, false),
+            AccountMeta::new_readonly(*source_authority, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::ExtendLock] instruction which moves [crate::state::ViewerStake::staked_until] forward keeping tokens and `staked_at`.
+/// Can be done only before lock lapsed. Rank reached by longer lock applies its reward terms if better.
+///
+/// Returns rank held by stake as borsh `Option<u8>` via return data.
+///
+/// Accounts:
+///  * `sysvar_clock`         - *program, implicit*
+///  * `stake_pool`           - *mutable* account of stake pool used
+///  * `viewer_stake_account` - *implicit, derived, mutable* from `user_wallet`, `stake_authority` and `input.position`
+///  * `user_wallet`          - *signer* owner of `viewer_stake_account`
+pub fn extend_lock(
+    stake_pool: &Pubkey,
+    user_wallet: &SignerPubkey,
+    input: ExtendLockInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let viewer_stake_account = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority,
+        user_wallet,
+        input.position,
+        &stake_viewer_program_id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::ExtendLock(input),
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new(viewer_stake_account.0, false),
+            AccountMeta::new_readonly(*user_wallet, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::FundRewards] instruction which transfers `input.amount` from `token_account_source` into `reward_reserve` of `stake_pool`.
+/// Creates `reward_reserve` on first funding to hold [crate::state::ViewerStakePool::reward_mint]. Anybody can fund rewards.
+///
+/// Accounts:
+///  * `system_program`           - *program, implicit* to create `reward_reserve`
+///  * `sysvar_rent`              - *program, implicit* to make `reward_reserve` rent exempt
+///  * `spl_token`                - *program, implicit*
+///  * `fee_payer`                - *signer, payer* pays for `reward_reserve` creation
+///  * `stake_pool`               - initialized stake pool
+///  * `stake_authority`          - *implicit, derived* as in [Instruction::InitializeStakePool]
+///  * `token_account_stake_pool` - *implicit, derived* to check mint of `reward_reserve`
+///  * `mint`                     - [crate::state::ViewerStakePool::reward_mint] or mint of `token_account_stake_pool` if it is default
+///  * `reward_reserve`           - *implicit, derived, mutable* token account created with seed `ViewerStakePool::reward_reserve` under `stake_authority`
+///  * `token_account_source`     - *mutable* tokens to fund
+///  * `source_authority`         - *signer* owner or delegate of `token_account_source`
+pub fn fund_rewards(
+    fee_payer: &SignerPubkey,
+    stake_pool: &Pubkey,
+    mint: &MintPubkey,
+    token_account_source: &TokenAccountPubkey,
+    source_authority: &SignerPubkey,
+    input: RewardsInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let token_account_stake_pool = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )?;
+    let reward_reserve = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::reward_reserve",
+        &spl_token::id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::FundRewards(input),
+        vec![
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*fee_payer, true),
+            AccountMeta::new_readonly(*stake_pool, false),
+            AccountMeta::new_readonly(stake_authority, false),
+            AccountMeta::new_readonly(token_account_stake_pool, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(reward_reserve, false),
+            AccountMeta::new(*token_account_source, false),
+            AccountMeta::new_readonly(*source_authority, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::WithdrawUnallocatedRewards] instruction which transfers `input.amount` from `reward_reserve` to `token_account_target`.
+/// Fails if `input.amount` exceeds [crate::state::ViewerStakePool::unallocated_rewards], so rewards owed to stakes and principal never leave pool.
+///
+/// Accounts:
+///  * `spl_token`            - *program, implicit*
+///  * `stake_pool`           - initialized stake pool
+///  * `stake_authority`      - *implicit, derived* as in [Instruction::InitializeStakePool]
+///  * `reward_reserve`       - *implicit, derived, mutable* as in [Instruction::FundRewards]
+///  * `token_account_target` - *mutable* receives withdrawn tokens
+///  * `stake_pool_owner`     - *signer* owner of `stake_pool`
+pub fn withdraw_unallocated_rewards(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    token_account_target: &TokenAccountPubkey,
+    input: RewardsInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let reward_reserve = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::reward_reserve",
+        &spl_token::id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::WithdrawUnallocatedRewards(input),
+        vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(*stake_pool, false),
+            AccountMeta::new_readonly(stake_authority, false),
+            AccountMeta::new(reward_reserve, false),
+            AccountMeta::new(*token_account_target, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
+
+/// Creates [Instruction::ClosePool] instruction which closes `stake_pool`, `token_account` and `reward_reserve` if any, rent of all goes to `destination`.
+/// Fails if any stake remains, including stakes of first version not migrated into totals yet, or `reward_reserve` is not withdrawn.
+///
+/// Closed positions are burned on unstake, but [crate::state::ViewerWallet] accounts and positions with zero amount created by older versions are not closed.
+/// They hold rent only and are derived from `stake_authority` of closed pool, so they are used again only if pool is created at same address.
+///
+/// Accounts:
+///  * `spl_token`                - *program, implicit*
+///  * `stake_pool`               - *mutable* initialized stake pool
+///  * `stake_authority`          - *implicit, derived* as in [Instruction::InitializeStakePool]
+///  * `token_account_stake_pool` - *implicit, derived, mutable* as in [Instruction::InitializeStakePool]
+///  * `reward_reserve`           - *implicit, derived, mutable* as in [Instruction::FundRewards]
+///  * `destination`              - *mutable* receives rent
+///  * `stake_pool_owner`         - *signer* owner of `stake_pool`
+pub fn close_pool(
+    stake_pool: &Pubkey,
+    stake_pool_owner: &SignerPubkey,
+    destination: &Pubkey,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(stake_pool, &stake_viewer_program_id());
+    let token_account_stake_pool = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )?;
+    let reward_reserve = Pubkey::create_with_seed(
+        &stake_authority,
+        "ViewerStakePool::reward_reserve",
+        &spl_token::id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::ClosePool,
+        vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(stake_authority, false),
+            AccountMeta::new(token_account_stake_pool, false),
+            AccountMeta::new(reward_reserve, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*stake_pool_owner, true),
+        ],
+    ))
+}
 
+/// Creates [Instruction::Migrate] instruction which upgrades `stake_pool` and `viewer_stake_accounts` of older [sator_sdk::state::StateVersion] in place.
+/// Accounts of current version are left as is. Migrated stakes are counted in pool totals and statistics.
+/// Anybody can migrate as data is kept.
+///
+/// Accounts:
+///  * `system_program`        - *program, implicit* to top up rent of grown accounts
+///  * `sysvar_rent`           - *program, implicit*
+///  * `fee_payer`             - *signer, mutable* pays rent of grown accounts
+///  * `stake_pool`            - *mutable* stake pool
+///  * `viewer_stake_accounts` - *mutable, collection* stakes of `stake_pool`
+pub fn migrate(
+    fee_payer: &SignerPubkey,
+    stake_pool: &Pubkey,
+    viewer_stake_accounts: &[Pubkey],
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*fee_payer, true),
+        AccountMeta::new(*stake_pool, false),
+    ];
+    for viewer_stake_account in viewer_stake_accounts {
+        accounts.push(AccountMeta::new(*viewer_stake_account, false));
+    }
     Ok(solana_program::instruction::Instruction::new_with_borsh(
         crate::id(),
-        &Instruction::Unstake,
+        &Instruction::Migrate,
         accounts,
     ))
 }
@@ -216,8 +1066,9 @@ pub fn unstake(
 mod tests {
     use crate::{types::Rank};
 
-    use super::{InitializeStakePoolInput, Instruction};
+    use super::{InitializeStakePoolInput, Instruction, UnstakeInput};
     use borsh::*;
+    use solana_program::pubkey::Pubkey;
 
     #[test]
     fn test() {
@@ -226,24 +1077,30 @@ mod tests {
                 Rank {
                     minimal_staking_time: 0,
                     amount: 100,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 30 * 60,
                     amount: 200,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 60 * 60,
                     amount: 300,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 2 * 60 * 60,
                     amount: 500,
+                    reward_multiplier: 0,
                 },
             ],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
         });
 
         let data = hex::encode(input.try_to_vec().unwrap());
-        assert_eq!(data, "00000000000000000064000000000000000807000000000000c800000000000000100e0000000000002c01000000000000201c000000000000f401000000000000");
+        assert_eq!(data, "000000000000000000640000000000000000000000000000000807000000000000c8000000000000000000000000000000100e0000000000002c010000000000000000000000000000201c000000000000f4010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
 
         #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
         pub struct InitializeStakePoolInput2 {
@@ -260,28 +1117,48 @@ mod tests {
                 Rank {
                     minimal_staking_time: 0,
                     amount: 100,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 30 * 60,
                     amount: 200,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 60 * 60,
                     amount: 300,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 2 * 60 * 60,
                     amount: 500,
+                    reward_multiplier: 0,
                 },
             ],
         });
 
         let data = hex::encode(input.try_to_vec().unwrap());
-        assert_eq!(data, "0004000000000000000000000064000000000000000807000000000000c800000000000000100e0000000000002c01000000000000201c000000000000f401000000000000");
+        assert_eq!(data, "00040000000000000000000000640000000000000000000000000000000807000000000000c8000000000000000000000000000000100e0000000000002c010000000000000000000000000000201c000000000000f4010000000000000000000000000000");
+
+        let input = Instruction::Unstake(UnstakeInput {
+            amount: None,
+            position: 0,
+        });
+
+        let data = hex::encode(input.try_to_vec().unwrap());
+        assert_eq!(data, "020000");
+
+        let input = Instruction::Unstake(UnstakeInput {
+            amount: Some(100),
+            position: 2,
+        });
+
+        let data = hex::encode(input.try_to_vec().unwrap());
+        assert_eq!(data, "0201640000000000000002");
 
-        let input = Instruction::Unstake;
+        let input = Instruction::GetRank;
 
         let data = hex::encode(input.try_to_vec().unwrap());
-        assert_eq!(data, "02");
+        assert_eq!(data, "03");
     }
 }
@@ -1,24 +1,42 @@
 #![feature(trivial_bounds)]
 //! Stake pool for viewers.
 //! - `StakePool.owner` can create stake pool, `owner` onwards.
+//! - `owner` hands over pool in two steps, `ProposeOwner` stores `pending_owner` and `AcceptOwner` signed by it makes it `owner`.
 //! - `owner` specificities if viewer KYC required to participate in staking.
-//! - Only `owner` can added KYC to `ViewerStakeAccount`. KYC is just flag, not detailed description of how she was KYCed.
+//! - Only `owner` can added KYC to `ViewerWallet`. KYC is just flag, not detailed description of how she was KYCed.
+//! - `SetViewerKyc` sets `ViewerWallet.kyc_verified`, creating `ViewerWallet` if viewer has not staked yet. `ViewerWallet` is never closed, so KYC outlives whole unstake of positions.
 //! - `owner` specifies minimal amount to stake and minimal time to stake. Up to 4 stake `ranks`.
 //! - For each `rank` `owner` specifies APY `reward_multiplier`. So `reward = stake time / year * amount * reward_multiplier`.
 //! - Default `reward_multiplier` is zero. `owner` can update `reward_multiplier` on `StakePool`. Existing `ViewerStakeAccount` rewards cannot be updated until unstake.
+//! - `UpdateRanks` lets `owner` retune ranks, `ViewerStake` keeps `reward_multiplier` of rank reached at stake time.
 //! - Viewer should stake at least minimal amount and time to stake to fit specified rank.
+//! - `Unstake` can take part of stake after lock lapsed, rest stays staked under same terms, whole unstake closes `ViewerStake`.
+//! - `EarlyUnstake` unstakes before lock lapsed without reward, withholding penalty set by `owner` when staked into pool `treasury`.
+//! - `SetPoolMode` lets `owner` pause staking of new tokens, or enable emergency mode in which viewers unstake principal without reward regardless of lock.
+//! - `GetRank` returns rank held by `ViewerStake` via return data, so ranks are matched on chain only.
 //! - Viewer can stake any amount, so minimal time should be not less than minimal for smallest rank.
 //! - Staked amount can be less than minimal rank requirement.
-//! - `owner` must top up stake `StakePool.token_account` to allow non zero `reward_multiplier`s.
-//! - Adding amount resets the timer to zero. Non resetting option is possible, but need to be discussed if need to be implemented now..
+//! - `SetStakeLimits` lets `owner` set pool wide `min_stake` of position, `max_stake_per_wallet` of all positions of wallet and total `capacity` of pool, checked on `Stake` and `AddToStake`.
+//! - `owner` must top up `reward_reserve` with `FundRewards` to allow non zero `reward_multiplier`s. Rewards are paid from `reward_reserve` only, `token_account` holds principal only.
+//! - `StakePool.reward_mint` set on creation lets pool take SAO as stake and pay rewards in other mint held by `reward_reserve`, `Unstake` pays reward to separate `reward_token_account_target`.
+//! - `StakePool.staker_count` and `StakePool.rank_staker_counts` count stake positions by rank reached at last change, so dashboards do not scan all `ViewerStake`s.
+//! - `StakePool.total_staked` and `StakePool.allocated_rewards` are tracked, so `WithdrawUnallocatedRewards` lets `owner` take back only rewards not owed to stakes.
+//! - Adding amount with `Stake` resets the timer to zero. `AddToStake` adds amount keeping active lock, added amount earns only for rest of lock under terms of rank reached for it.
+//! - `ExtendLock` moves end of lock forward before it lapsed, so rank can be upgraded without unstake.
+//! - Wallet can have independent indexed positions, each with own amount, lock and terms. `GetRank` sums positions passed to it.
 //! - Allowing rewards depending on count of passed quizzes is possible, but should be discussed if should be implemented now.
-//! - Anybody can stake for Viewer if it does not resets its stake rank. Only Viewer can add to stake if it resets stake rank.
+//! - `DelegatedStake` lets `owner` stake tokens Viewer approved to `stake_authority` as SPL delegate, so backend stakes from Viewer own token account without Viewer keys, only into new position for minimal staking time of first rank.
+//! - `StakeWithIntent` and `UnstakeWithIntent` execute intent Viewer signed off chain, verified by preceding Ed25519 program instruction, so relayer pays fees. Signed message binds intent to program and pool, `ViewerWallet.nonce` never reset by closing positions and intent expiry prevent replay.
+//! - Anybody can stake for Viewer if it does not resets its stake rank (`AddToStake`). Only Viewer can add to stake if it resets stake rank.
 //! - `Claim` can be done by `Viewer` signature to any address or via permissionless call to `associated token account` for SAO mint on `Viewer` wallet (`ViewerStake.owner`).
 //! - `TypeScript` sdk to call on chain program is coded with example to call each instruction.
 //! - So `ViewerStake` is operation under `owner` or `Viewer` credentials.
+//! - `ClosePool` lets `owner` close pool without stakes, its `token_account` and withdrawn `reward_reserve`, returning rent to any destination. `ViewerWallet`s are not closed.
+//! - `Migrate` upgrades `StakePool` and `ViewerStake` accounts of older `StateVersion` in place, anybody can pay for it.
 //! - `stake_authority` is derived operations are signed by on chain stake derived signature
-//! - `stake_pool`'s `token_account` is derived from and owned by `stake_authority`
+//! - `stake_pool`'s `token_account` and `reward_reserve` are derived from and owned by `stake_authority`
 //! - `stake_viewer_account` for each user is derived from wallet and pool
+//! - `viewer_wallet` for each user is derived from wallet and pool, created by first `Stake`, `AddToStake` or `SetViewerKyc`
 //!
 //!```rust, ignore
 //! let stake_pool = Pubkey::new_unique();
@@ -33,6 +51,12 @@
 //! let seed = user_wallet.to_bytes();
 //! let seed = bs58::encode(&seed[..20]).into_string();
 //! let viewer_stake_account = Pubkey::create_with_seed(stake_authority, &seed, &stake_viewer_program_id());
+//! // positions other than first append index
+//! let position_seed = format!("{}_{}", seed, position);
+//! let viewer_stake_position_account = Pubkey::create_with_seed(stake_authority, &position_seed, &stake_viewer_program_id());
+//! // wallet state shared by positions
+//! let wallet_seed = format!("{}_w", seed);
+//! let viewer_wallet = Pubkey::create_with_seed(stake_authority, &wallet_seed, &stake_viewer_program_id());
 //!```
 
 pub mod entrypoint;
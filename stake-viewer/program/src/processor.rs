@@ -1,19 +1,24 @@
 use sator_sdk::{borsh::*, ensure};
+use sator_sdk::ed25519;
 use sator_sdk::invoke::{self, ProgramPubkeySignature};
-use sator_sdk::state::StateVersion;
+use sator_sdk::state::{migrate_state, StateVersion};
 use sator_sdk::types::*;
 use sator_sdk::{ensure_derived, ensure_eq, ensure_owner, program::*};
-use solana_program::clock::Clock;
+use solana_program::clock::{Clock, UnixTimestamp};
 use solana_program::msg;
+use solana_program::program::set_return_data;
 use solana_program::program_error::ProgramError;
+use solana_program::program_option::COption;
 use solana_program::program_pack::Pack;
 use solana_program::rent::Rent;
 use solana_program::sysvar::Sysvar;
 use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
 
+use borsh::BorshSerialize;
+
 use crate::errors;
 use crate::instruction::Instruction;
-use crate::state::{ViewerStake, ViewerStakePool};
+use crate::state::{ViewerStake, ViewerStakePool, ViewerWallet};
 
 // Program entrypoint's implementation
 #[allow(dead_code)]
@@ -59,10 +64,11 @@ pub fn process_instruction(
                     token_account_source,
                     token_account_stake_target,
                     viewer_stake_account,
+                    viewer_wallet,
                     user_wallet,
                     ..
                 ] => {
-                    let stake_pool_owner = accounts.get(11);
+                    let stake_pool_owner = accounts.get(12);
                     stake(
                         program_id,
                         system_program,
@@ -75,16 +81,115 @@ pub fn process_instruction(
                         token_account_source,
                         token_account_stake_target,
                         viewer_stake_account,
+                        viewer_wallet,
                         user_wallet,
                         stake_pool_owner,                    
                         input,
+                        false,
+                        None,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::DelegatedStake(input) => {
+            msg!("Instruction::DelegatedStake");
+            match accounts {
+                [
+                    system_program,
+                    sysvar_rent,
+                    clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_source,
+                    token_account_stake_target,
+                    viewer_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    stake_pool_owner,
+                    ..
+                ] => stake(
+                    program_id,
+                    system_program,
+                    sysvar_rent,
+                    clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_source,
+                    token_account_stake_target,
+                    viewer_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    Some(stake_pool_owner),
+                    input,
+                    true,
+                    None,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::StakeWithIntent(input) => {
+            msg!("Instruction::StakeWithIntent");
+            match accounts {
+                [
+                    system_program,
+                    sysvar_rent,
+                    clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_source,
+                    token_account_stake_target,
+                    viewer_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    sysvar_instructions,
+                    ..
+                ] => {
+                    verify_intent(
+                        program_id,
+                        sysvar_instructions,
+                        clock,
+                        stake_pool,
+                        user_wallet,
+                        instruction_data,
+                        input.valid_until,
+                    )?;
+                    ensure!(
+                        input.stake_pool == stake_pool.pubkey()
+                            && input.token_account_source == token_account_source.pubkey(),
+                        errors::Error::IntentMustMatchAccounts
+                    );
+                    stake(
+                        program_id,
+                        system_program,
+                        sysvar_rent,
+                        clock,
+                        spl_token,
+                        fee_payer,
+                        stake_pool,
+                        stake_authority,
+                        token_account_source,
+                        token_account_stake_target,
+                        viewer_stake_account,
+                        viewer_wallet,
+                        user_wallet,
+                        None,
+                        input.stake,
+                        true,
+                        Some(input.nonce),
                     )
                 }
                 _ => Err(ProgramError::NotEnoughAccountKeys),
             }
         }
 
-        Instruction::Unstake => {
+        Instruction::Unstake(input) => {
             msg!("Instruction::Unstake");
             match accounts {
                 [
@@ -95,11 +200,14 @@ pub fn process_instruction(
                     stake_authority,
                     token_account_target,
                     token_account_stake_source,
+                    reward_reserve,
+                    reward_token_account_target,
                     user_stake_account,
+                    viewer_wallet,
                     user_wallet,
                     ..
                 ] => {
-                    let stake_pool_owner = accounts.get(9);
+                    let stake_pool_owner = accounts.get(12);
                     unstake(
                         program_id,
                         sysvar_clock,
@@ -109,271 +217,1523 @@ stake_pool,
 stake_authority,
 token_account_target,
 token_account_stake_source,
+reward_reserve,
+reward_token_account_target,
 user_stake_account,
+viewer_wallet,
 user_wallet,
 stake_pool_owner,
+                        input,
+                        None,
                     )
                 }
                 _ => Err(ProgramError::NotEnoughAccountKeys),
             }
         }
-    }
-}
-
-fn initialize_stake_pool<'a>(
-    program_id: &ProgramPubkey,
-    system_program: &AccountInfo<'a>,
-    rent: &AccountInfo<'a>,
-    spl_token: &AccountInfo<'a>,
-    fee_payer: &AccountInfo<'a>,
-    stake_pool_owner: &AccountInfo<'a>,
-    stake_pool: &AccountInfo<'a>,
-    stake_authority: &AccountInfo<'a>,
-    token_account: &AccountInfo<'a>,
-    mint: &AccountInfo<'a>,
-    input: &crate::instruction::InitializeStakePoolInput,
-) -> ProgramResult {
-    stake_pool_owner.is_signer()?;
-    stake_pool.is_signer()?;
-    let (stake_authority_pubkey, bump_seed, token_account_pubkey) =
-        derive_token_account(stake_pool, program_id)?;
-
-    is_derived(stake_authority_pubkey, stake_authority)?;
-    is_derived(token_account_pubkey, token_account)?;
-
-    let rent_state = Rent::from_account_info(rent)?;
-    let lamports = rent_state.minimum_balance(ViewerStakePool::LEN);
-    invoke::create_account(
-        fee_payer.clone(),
-        stake_pool.clone(),
-        lamports,
-        ViewerStakePool::LEN as u64,
-        program_id,
-        system_program,
-        spl_token,
-    )?;
-
-    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
-
-    let lamports = rent_state.minimum_balance(spl_token::state::Account::LEN);
-    invoke::create_account_with_seed_signed(
-        system_program,
-        &fee_payer,
-        &token_account,
-        stake_authority,
-        "ViewerStakePool::token_account",
-        lamports,
-        spl_token::state::Account::LEN as u64,
-        &spl_token::id(),
-        &authority_signature,
-    )?;
-
-    invoke::initialize_token_account_signed(
-        token_account,
-        &mint,
-        stake_authority,
-        rent,
-        &authority_signature,
-    )?;
-
-    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
-    state.ranks = input.ranks.clone();
-    state.owner = stake_pool_owner.pubkey();
-    state.version = StateVersion::V1;
-    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
-
-    Ok(())
-}
-
-fn derive_token_account(
-    stake: &AccountInfo,
-    program_id: &ProgramPubkey,
-) -> Result<(Pubkey, u8, Pubkey), ProgramError> {
-    let (stake_authority_pubkey, bump_seed) = {
-        let (stake_authority_pubkey, bump_seed) =
-            Pubkey::find_program_address_for_pubkey(&stake.pubkey(), program_id);
-        (stake_authority_pubkey, bump_seed)
-    };
-    let derived = Pubkey::create_with_seed(
-        &stake_authority_pubkey,
-        "ViewerStakePool::token_account",
-        &spl_token::id(),
-    )?;
-    Ok((stake_authority_pubkey, bump_seed, derived))
-}
-
-pub fn stake<'a>(
-    program_id: &ProgramPubkey,
-    system_program: &AccountInfo<'a>,
-    sysvar_rent: &AccountInfo<'a>,
-    clock: &AccountInfo<'a>,
-    spl_token: &AccountInfo<'a>,
-    fee_payer: &AccountInfo<'a>,
-    stake_pool: &AccountInfo<'a>,
-    stake_authority: &AccountInfo<'a>,
-    token_account_source: &AccountInfo<'a>,
-    token_account_stake_target: &AccountInfo<'a>,
-    viewer_stake_account: &AccountInfo<'a>,
-    user_wallet: &AccountInfo<'a>,
-    stake_pool_owner: Option<&AccountInfo<'a>>,
-    input: crate::instruction::StakeInput,
-) -> ProgramResult {
-    ensure_eq!(
-        program_id,
-        stake_pool.owner,
-        errors::Error::StakePoolMustBeOwnedByThisContract
-    );
-    let stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
-    stake_pool_state.initialized()?;
-    if let Some(stake_pool_owner) = stake_pool_owner {
-        ensure_owner!(
-            stake_pool_owner,
-            stake_pool_state,
-            errors::Error::StakePoolOwnerMustOwnStake
-        );    
-        stake_pool_owner.is_signer()?;
-    } else {
-        user_wallet.is_signer()?;
-    }
-
-    let can_restake = user_wallet.is_signer().is_ok();
-    ensure!(
-        can_restake || stake_pool_owner.map_or(false, |x| x.is_signer().is_ok()),
-        errors::Error::StakeForViewerMustBeSignedByUserWalletOrPoolAdmin
-    );
-    
-    let clock = Clock::from_account_info(clock)?;
-    if input.duration < stake_pool_state.ranks[0].minimal_staking_time {
-        return errors::Error::StakeStakingTimeMustBeMoreThanMinimal.into();
-    }
-
-    let (stake_authority_pubkey, bump_seed, token_account_pubkey) =
-        derive_token_account(stake_pool, program_id)?;
-
-    let (viewer_stake_account_pubkey, seed) = Pubkey::create_with_seed_for_pubkey(
-        &stake_authority_pubkey,
-        &user_wallet.pubkey(),
-        program_id,
-    )?;
-
-    ensure_derived!(
-        stake_authority_pubkey,
-        stake_authority,
-        errors::Error::StakeAuthorityMustBeDerivedFromStake
-    );
-    ensure_derived!(
-        token_account_pubkey,
-        token_account_stake_target,
-        errors::Error::StakeTokenAccountMustBeDerivedFromStake
-    );
-    ensure_derived!(
-        viewer_stake_account_pubkey,
-        viewer_stake_account,
-        errors::Error::StakeUserMustBeDerivedFromUserToken
-    );
-
-    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
-    let stake_user_account_state = if viewer_stake_account.data_is_empty() {        
-        // new stake
-        let stake_user_account_state = ViewerStake {
-            amount: input.amount,
-            owner: user_wallet.pubkey(),
-            staked_until: clock.unix_timestamp + input.duration,
-            version: StateVersion::V1,
-            staked_at: clock.unix_timestamp,
-        };
-        let rent_state = Rent::from_account_info(sysvar_rent)?;
-        let lamports = rent_state.minimum_balance(ViewerStake::LEN);
-        invoke::create_account_with_seed_signed(
-            system_program,
-            &fee_payer,
-            &viewer_stake_account,
-            stake_authority,
-            &seed[..],
-            lamports,
-            ViewerStake::LEN as u64,
-            program_id,
-            &authority_signature,
-        )?;
-        stake_user_account_state
-    } else {
-        ensure_eq!(
-            program_id,
-            viewer_stake_account.owner,
-            errors::Error::StakeUserAccountMustBeOwnedByThisContract
-        );
-        let mut stake_user_account_state = viewer_stake_account.deserialize::<ViewerStake>()?;
-        stake_user_account_state.initialized()?;
-
-        if input.duration < stake_user_account_state.duration() {
-            return errors::Error::StakeStakingTimeMustBeMoreThanPrevious.into();
-        }
-
-        ensure_owner!(
+        Instruction::UnstakeWithIntent(input) => {
+            msg!("Instruction::UnstakeWithIntent");
+            match accounts {
+                [
+                    sysvar_clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_target,
+                    token_account_stake_source,
+                    reward_reserve,
+                    reward_token_account_target,
+                    user_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    sysvar_instructions,
+                    ..
+                ] => {
+                    verify_intent(
+                        program_id,
+                        sysvar_instructions,
+                        sysvar_clock,
+                        stake_pool,
+                        user_wallet,
+                        instruction_data,
+                        input.valid_until,
+                    )?;
+                    ensure!(
+                        input.stake_pool == stake_pool.pubkey()
+                            && input.token_account_target == token_account_target.pubkey()
+                            && input.reward_token_account_target
+                                == reward_token_account_target.pubkey(),
+                        errors::Error::IntentMustMatchAccounts
+                    );
+                    unstake(
+                        program_id,
+                        sysvar_clock,
+                        spl_token,
+                        fee_payer,
+                        stake_pool,
+                        stake_authority,
+                        token_account_target,
+                        token_account_stake_source,
+                        reward_reserve,
+                        reward_token_account_target,
+                        user_stake_account,
+                        viewer_wallet,
+                        user_wallet,
+                        None,
+                        input.unstake,
+                        Some(input.nonce),
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::GetRank => {
+            msg!("Instruction::GetRank");
+            match accounts {
+                [sysvar_clock, stake_pool, _, ..] => {
+                    let viewer_stake_accounts = accounts.iter().skip(2);
+                    get_rank(program_id, sysvar_clock, stake_pool, viewer_stake_accounts)
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::UpdateRanks(input) => {
+            msg!("Instruction::UpdateRanks");
+            match accounts {
+                [stake_pool, stake_pool_owner, ..] => {
+                    update_ranks(program_id, stake_pool, stake_pool_owner, &input)
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::SetPoolMode(input) => {
+            msg!("Instruction::SetPoolMode");
+            match accounts {
+                [stake_pool, stake_pool_owner, ..] => {
+                    set_pool_mode(program_id, stake_pool, stake_pool_owner, &input)
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::FundRewards(input) => {
+            msg!("Instruction::FundRewards");
+            match accounts {
+                [
+                    system_program,
+                    sysvar_rent,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_stake_pool,
+                    mint,
+                    reward_reserve,
+                    token_account_source,
+                    source_authority,
+                    ..
+                ] => fund_rewards(
+                    program_id,
+                    system_program,
+                    sysvar_rent,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_stake_pool,
+                    mint,
+                    reward_reserve,
+                    token_account_source,
+                    source_authority,
+                    &input,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::WithdrawUnallocatedRewards(input) => {
+            msg!("Instruction::WithdrawUnallocatedRewards");
+            match accounts {
+                [
+                    spl_token,
+                    stake_pool,
+                    stake_authority,
+                    reward_reserve,
+                    token_account_target,
+                    stake_pool_owner,
+                    ..
+                ] => withdraw_unallocated_rewards(
+                    program_id,
+                    spl_token,
+                    stake_pool,
+                    stake_authority,
+                    reward_reserve,
+                    token_account_target,
+                    stake_pool_owner,
+                    &input,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::ClosePool => {
+            msg!("Instruction::ClosePool");
+            match accounts {
+                [
+                    spl_token,
+                    stake_pool,
+                    stake_authority,
+                    token_account_stake_pool,
+                    reward_reserve,
+                    destination,
+                    stake_pool_owner,
+                    ..
+                ] => close_pool(
+                    program_id,
+                    spl_token,
+                    stake_pool,
+                    stake_authority,
+                    token_account_stake_pool,
+                    reward_reserve,
+                    destination,
+                    stake_pool_owner,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::Migrate => {
+            msg!("Instruction::Migrate");
+            match accounts {
+                [system_program, sysvar_rent, fee_payer, stake_pool, ..] => {
+                    let viewer_stake_accounts = accounts.iter().skip(4);
+                    migrate(
+                        program_id,
+                        system_program,
+                        sysvar_rent,
+                        fee_payer,
+                        stake_pool,
+                        viewer_stake_accounts,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::SetStakeLimits(input) => {
+            msg!("Instruction::SetStakeLimits");
+            match accounts {
+                [stake_pool, stake_pool_owner, ..] => {
+                    set_stake_limits(program_id, stake_pool, stake_pool_owner, &input)
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::ProposeOwner(input) => {
+            msg!("Instruction::ProposeOwner");
+            match accounts {
+                [stake_pool, stake_pool_owner, ..] => {
+                    propose_owner(program_id, stake_pool, stake_pool_owner, &input)
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::AcceptOwner => {
+            msg!("Instruction::AcceptOwner");
+            match accounts {
+                [stake_pool, pending_owner, ..] => accept_owner(program_id, stake_pool, pending_owner),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::SetEarlyUnstakePenalty(input) => {
+            msg!("Instruction::SetEarlyUnstakePenalty");
+            match accounts {
+                [stake_pool, stake_pool_owner, token_account_stake_pool, treasury, ..] => {
+                    set_early_unstake_penalty(
+                        program_id,
+                        stake_pool,
+                        stake_pool_owner,
+                        token_account_stake_pool,
+                        treasury,
+                        &input,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::EarlyUnstake(input) => {
+            msg!("Instruction::EarlyUnstake");
+            match accounts {
+                [
+                    sysvar_clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_target,
+                    token_account_stake_source,
+                    user_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    treasury,
+                    ..
+                ] => early_unstake(
+                    program_id,
+                    sysvar_clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_target,
+                    token_account_stake_source,
+                    user_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    treasury,
+                    input,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::AddToStake(input) => {
+            msg!("Instruction::AddToStake");
+            match accounts {
+                [
+                    system_program,
+                    sysvar_rent,
+                    sysvar_clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_source,
+                    token_account_stake_target,
+                    viewer_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    source_authority,
+                    ..
+                ] => add_to_stake(
+                    program_id,
+                    system_program,
+                    sysvar_rent,
+                    sysvar_clock,
+                    spl_token,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    token_account_source,
+                    token_account_stake_target,
+                    viewer_stake_account,
+                    viewer_wallet,
+                    user_wallet,
+                    source_authority,
+                    input,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::ExtendLock(input) => {
+            msg!("Instruction::ExtendLock");
+            match accounts {
+                [sysvar_clock, stake_pool, viewer_stake_account, user_wallet, ..] => extend_lock(
+                    program_id,
+                    sysvar_clock,
+                    stake_pool,
+                    viewer_stake_account,
+                    user_wallet,
+                    input,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::SetViewerKyc(input) => {
+            msg!("Instruction::SetViewerKyc");
+            match accounts {
+                [
+                    system_program,
+                    sysvar_rent,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    viewer_wallet,
+                    user_wallet,
+                    stake_pool_owner,
+                    ..
+                ] => set_viewer_kyc(
+                    program_id,
+                    system_program,
+                    sysvar_rent,
+                    fee_payer,
+                    stake_pool,
+                    stake_authority,
+                    viewer_wallet,
+                    user_wallet,
+                    stake_pool_owner,
+                    &input,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+    }
+}
+
+fn initialize_stake_pool<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    rent: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool_owner: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    token_account: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    input: &crate::instruction::InitializeStakePoolInput,
+) -> ProgramResult {
+    stake_pool_owner.is_signer()?;
+    stake_pool.is_signer()?;
+    ensure!(
+        ViewerStakePool::ranks_ordered(&input.ranks),
+        errors::Error::RanksMustBeOrderedByTimeAndAmount
+    );
+    let (stake_authority_pubkey, bump_seed, token_account_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
+
+    is_derived(stake_authority_pubkey, stake_authority)?;
+    is_derived(token_account_pubkey, token_account)?;
+
+    let rent_state = Rent::from_account_info(rent)?;
+    let lamports = rent_state.minimum_balance(ViewerStakePool::LEN);
+    invoke::create_account(
+        fee_payer.clone(),
+        stake_pool.clone(),
+        lamports,
+        ViewerStakePool::LEN as u64,
+        program_id,
+        system_program,
+        spl_token,
+    )?;
+
+    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+
+    let lamports = rent_state.minimum_balance(spl_token::state::Account::LEN);
+    invoke::create_account_with_seed_signed(
+        system_program,
+        &fee_payer,
+        &token_account,
+        stake_authority,
+        "ViewerStakePool::token_account",
+        lamports,
+        spl_token::state::Account::LEN as u64,
+        &spl_token::id(),
+        &authority_signature,
+    )?;
+
+    invoke::initialize_token_account_signed(
+        token_account,
+        &mint,
+        stake_authority,
+        rent,
+        &authority_signature,
+    )?;
+
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.ranks = input.ranks.clone();
+    state.kyc_required = input.kyc_required;
+    state.owner = stake_pool_owner.pubkey();
+    state.reward_mint = input.reward_mint;
+    state.version = StateVersion::V4;
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Ensures `user_wallet` signed [crate::instruction::intent_message] of `instruction_data` as message of Ed25519 instruction preceding current one, and intent is not expired
+fn verify_intent(
+    program_id: &ProgramPubkey,
+    sysvar_instructions: &AccountInfo,
+    sysvar_clock: &AccountInfo,
+    stake_pool: &AccountInfo,
+    user_wallet: &AccountInfo,
+    instruction_data: &[u8],
+    valid_until: UnixTimestamp,
+) -> ProgramResult {
+    let (signer, message) = ed25519::previous_signed_message(sysvar_instructions)?;
+    let intent_message =
+        crate::instruction::intent_message(program_id, &stake_pool.pubkey(), instruction_data);
+    ensure!(
+        signer == user_wallet.pubkey() && message == intent_message,
+        errors::Error::IntentMustBeSignedByUserWallet
+    );
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    ensure!(
+        clock.unix_timestamp <= valid_until,
+        errors::Error::IntentExpired
+    );
+    Ok(())
+}
+
+fn derive_token_account(
+    stake: &AccountInfo,
+    program_id: &ProgramPubkey,
+) -> Result<(Pubkey, u8, Pubkey), ProgramError> {
+    let (stake_authority_pubkey, bump_seed) = {
+        let (stake_authority_pubkey, bump_seed) =
+            Pubkey::find_program_address_for_pubkey(&stake.pubkey(), program_id);
+        (stake_authority_pubkey, bump_seed)
+    };
+    let derived = Pubkey::create_with_seed(
+        &stake_authority_pubkey,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )?;
+    Ok((stake_authority_pubkey, bump_seed, derived))
+}
+
+fn derive_reward_reserve(stake_authority: &Pubkey) -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::create_with_seed(
+        stake_authority,
+        "ViewerStakePool::reward_reserve",
+        &spl_token::id(),
+    )?)
+}
+
+/// Replaces `before` state of position with `after` in total of [ViewerWallet] of `user_wallet` and uses `intent_nonce` if any.
+/// Wallet is not created on unstake, positions staked before it was created are not counted in it anyway.
+fn account_viewer_wallet_stake(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    viewer_wallet: &AccountInfo,
+    user_wallet: &AccountInfo,
+    before: &ViewerStake,
+    after: &ViewerStake,
+    intent_nonce: Option<u64>,
+) -> ProgramResult {
+    let (stake_authority_pubkey, _, _) = derive_token_account(stake_pool, program_id)?;
+    let (viewer_wallet_pubkey, _) =
+        ViewerWallet::derive(&stake_authority_pubkey, &user_wallet.pubkey(), program_id)?;
+    ensure_derived!(
+        viewer_wallet_pubkey,
+        viewer_wallet,
+        errors::Error::ViewerWalletMustBeDerivedFromUserWallet
+    );
+    if viewer_wallet.data_is_empty() {
+        // intents are accepted only with nonce kept by wallet
+        ensure!(
+            intent_nonce.is_none(),
+            errors::Error::IntentNonceMustMatchWallet
+        );
+        return Ok(());
+    }
+    ensure_eq!(
+        program_id,
+        viewer_wallet.owner,
+        errors::Error::ViewerWalletMustBeOwnedByThisContract
+    );
+    let mut viewer_wallet_state = viewer_wallet.deserialize::<ViewerWallet>()?;
+    viewer_wallet_state.initialized()?;
+    viewer_wallet_state
+        .account_stake(before, after)
+        .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?;
+    if let Some(nonce) = intent_nonce {
+        viewer_wallet_state
+            .use_nonce(nonce)
+            .ok_or::<ProgramError>(errors::Error::IntentNonceMustMatchWallet.into())?;
+    }
+    viewer_wallet_state.serialize_const(&mut *viewer_wallet.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+/// Reads [ViewerWallet] of `user_wallet`, creating it on first use
+#[allow(clippy::too_many_arguments)]
+fn load_or_create_viewer_wallet<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    viewer_wallet: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+) -> Result<ViewerWallet, ProgramError> {
+    let (stake_authority_pubkey, bump_seed, _) = derive_token_account(stake_pool, program_id)?;
+    let (viewer_wallet_pubkey, seed) =
+        ViewerWallet::derive(&stake_authority_pubkey, &user_wallet.pubkey(), program_id)?;
+    ensure_derived!(
+        stake_authority_pubkey,
+        stake_authority,
+        errors::Error::StakeAuthorityMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        viewer_wallet_pubkey,
+        viewer_wallet,
+        errors::Error::ViewerWalletMustBeDerivedFromUserWallet
+    );
+
+    if viewer_wallet.data_is_empty() {
+        let rent_state = Rent::from_account_info(sysvar_rent)?;
+        let lamports = rent_state.minimum_balance(ViewerWallet::LEN);
+        invoke::create_account_with_seed_signed(
+            system_program,
+            fee_payer,
+            viewer_wallet,
+            stake_authority,
+            &seed[..],
+            lamports,
+            ViewerWallet::LEN as u64,
+            program_id,
+            &ProgramPubkeySignature::new(stake_pool, bump_seed),
+        )?;
+        Ok(ViewerWallet {
+            version: StateVersion::V1,
+            owner: user_wallet.pubkey(),
+            ..Default::default()
+        })
+    } else {
+        ensure_eq!(
+            program_id,
+            viewer_wallet.owner,
+            errors::Error::ViewerWalletMustBeOwnedByThisContract
+        );
+        let viewer_wallet_state = viewer_wallet.deserialize::<ViewerWallet>()?;
+        viewer_wallet_state.initialized()?;
+        Ok(viewer_wallet_state)
+    }
+}
+
+pub fn stake<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    token_account_source: &AccountInfo<'a>,
+    token_account_stake_target: &AccountInfo<'a>,
+    viewer_stake_account: &AccountInfo<'a>,
+    viewer_wallet: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    stake_pool_owner: Option<&AccountInfo<'a>>,
+    input: crate::instruction::StakeInput,
+    delegated: bool,
+    intent_nonce: Option<u64>,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    stake_pool_state.accepts_deposits()?;
+    if let Some(stake_pool_owner) = stake_pool_owner {
+        ensure_owner!(
+            stake_pool_owner,
+            stake_pool_state,
+            errors::Error::StakePoolOwnerMustOwnStake
+        );    
+        stake_pool_owner.is_signer()?;
+    } else if intent_nonce.is_none() {
+        user_wallet.is_signer()?;
+    }
+
+    // verified intent is signature of `user_wallet`
+    let can_restake = user_wallet.is_signer().is_ok() || intent_nonce.is_some();
+    ensure!(
+        can_restake || stake_pool_owner.map_or(false, |x| x.is_signer().is_ok()),
+        errors::Error::StakeForViewerMustBeSignedByUserWalletOrPoolAdmin
+    );
+    
+    let clock = Clock::from_account_info(clock)?;
+    if input.duration < stake_pool_state.ranks[0].minimal_staking_time {
+        return errors::Error::StakeStakingTimeMustBeMoreThanMinimal.into();
+    }
+    // viewer only approved tokens, so owner cannot choose longer lock or reset existing one
+    let delegated_by_owner = delegated && !can_restake;
+    ensure!(
+        !delegated_by_owner || input.duration == stake_pool_state.ranks[0].minimal_staking_time,
+        errors::Error::DelegatedStakeMustUseMinimalStakingTime
+    );
+
+    let (stake_authority_pubkey, bump_seed, token_account_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
+
+    let (viewer_stake_account_pubkey, seed) = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority_pubkey,
+        &user_wallet.pubkey(),
+        input.position,
+        program_id,
+    )?;
+
+    ensure_derived!(
+        stake_authority_pubkey,
+        stake_authority,
+        errors::Error::StakeAuthorityMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        token_account_pubkey,
+        token_account_stake_target,
+        errors::Error::StakeTokenAccountMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        viewer_stake_account_pubkey,
+        viewer_stake_account,
+        errors::Error::StakeUserMustBeDerivedFromUserToken
+    );
+
+    let mut viewer_wallet_state = load_or_create_viewer_wallet(
+        program_id,
+        system_program,
+        sysvar_rent,
+        fee_payer,
+        stake_pool,
+        stake_authority,
+        viewer_wallet,
+        user_wallet,
+    )?;
+    ensure!(
+        !stake_pool_state.kyc_required || viewer_wallet_state.kyc_verified,
+        errors::Error::StakeViewerMustBeKycVerified
+    );
+
+    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+    let mut stake_user_account_state_before = ViewerStake::default();
+    let mut stake_user_account_state = if viewer_stake_account.data_is_empty() {        
+        // new stake
+        let stake_user_account_state = ViewerStake {
+            amount: input.amount,
+            owner: user_wallet.pubkey(),
+            staked_until: clock.unix_timestamp + input.duration,
+            version: StateVersion::V2,
+            staked_at: clock.unix_timestamp,
+            reward_multiplier: stake_pool_state.reward_multiplier(input.amount, input.duration),
+            position: input.position,
+            late_stake_seconds: 0,
+            rank: ViewerStake::NO_RANK,
+            early_unstake_penalty_bps: stake_pool_state.early_unstake_penalty_bps,
+            carried_reward: 0,
+        };
+        let rent_state = Rent::from_account_info(sysvar_rent)?;
+        let lamports = rent_state.minimum_balance(ViewerStake::LEN);
+        invoke::create_account_with_seed_signed(
+            system_program,
+            &fee_payer,
+            &viewer_stake_account,
+            stake_authority,
+            &seed[..],
+            lamports,
+            ViewerStake::LEN as u64,
+            program_id,
+            &authority_signature,
+        )?;
+        stake_user_account_state
+    } else {
+        ensure_eq!(
+            program_id,
+            viewer_stake_account.owner,
+            errors::Error::StakeUserAccountMustBeOwnedByThisContract
+        );
+        let mut stake_user_account_state = viewer_stake_account.deserialize::<ViewerStake>()?;
+        stake_user_account_state.initialized()?;
+        stake_user_account_state_before = stake_user_account_state.clone();
+        ensure!(
+            !delegated_by_owner,
+            errors::Error::DelegatedStakeMustNotRestake
+        );
+
+        if input.duration < stake_user_account_state.duration() {
+            return errors::Error::StakeStakingTimeMustBeMoreThanPrevious.into();
+        }
+
+        ensure_owner!(
             user_wallet,
             stake_user_account_state,
             errors::Error::UserWalletMustBeOwnerOfViewerStakeAccount
         );
+        // reward earned so far is kept, new lock earns only from now
+        stake_user_account_state.carried_reward = stake_user_account_state
+            .reward_at(clock.unix_timestamp)
+            .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
         stake_user_account_state.staked_until = clock.unix_timestamp + input.duration;
         // existing stake just adds on top
         stake_user_account_state.amount += input.amount;
         stake_user_account_state.staked_at = clock.unix_timestamp;
+        // whole amount is staked from now
+        stake_user_account_state.late_stake_seconds = 0;
+        // restake accepts current terms of pool
+        stake_user_account_state.reward_multiplier = stake_pool_state.reward_multiplier(
+            stake_user_account_state.amount,
+            input.duration,
+        );
+        stake_user_account_state.early_unstake_penalty_bps =
+            stake_pool_state.early_unstake_penalty_bps;
         stake_user_account_state
     };
 
-    // transfer amount from provided user token account into stake pool
-    let signer = if user_wallet.is_signer {
-        user_wallet
+    if let Some(nonce) = intent_nonce {
+        viewer_wallet_state
+            .use_nonce(nonce)
+            .ok_or::<ProgramError>(errors::Error::IntentNonceMustMatchWallet.into())?;
+    }
+
+    // transfer amount from provided user token account into stake pool
+    if delegated {
+        // only tokens of staking wallet approved to pool can be taken
+        let token_account_source_state =
+            spl_token::state::Account::unpack(&token_account_source.try_borrow_data()?)?;
+        ensure_eq!(
+            token_account_source_state.owner,
+            user_wallet.pubkey(),
+            errors::Error::DelegatedStakeSourceMustBeOwnedByUserWallet
+        );
+        ensure!(
+            token_account_source_state.delegate == COption::Some(stake_authority_pubkey)
+                && token_account_source_state.delegated_amount >= input.amount,
+            errors::Error::DelegatedStakeMustBeApprovedToStakeAuthority
+        );
+        invoke::spl_token_transfer_signed(
+            spl_token,
+            token_account_source,
+            token_account_stake_target,
+            stake_authority,
+            input.amount,
+            &authority_signature,
+        )?;
+    } else {
+        let signer = if user_wallet.is_signer {
+            user_wallet
+        }
+        else {
+            stake_pool_owner.unwrap()
+        };
+
+        invoke::spl_token_transfer(
+            spl_token,
+            token_account_source,
+            token_account_stake_target,
+            signer,
+            input.amount,
+        )?;
+    }
+    stake_pool_state
+        .account_stake(&stake_user_account_state_before, &mut stake_user_account_state)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    viewer_wallet_state
+        .account_stake(&stake_user_account_state_before, &stake_user_account_state)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    stake_pool_state.check_stake_limits(&stake_user_account_state, &viewer_wallet_state)?;
+    stake_user_account_state.serialize_const(&mut *viewer_stake_account.try_borrow_mut_data()?)?;
+    viewer_wallet_state.serialize_const(&mut *viewer_wallet.try_borrow_mut_data()?)?;
+    stake_pool_state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn unstake<'a>(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    token_account_target: &AccountInfo<'a>,
+    token_account_stake_source: &AccountInfo<'a>,
+    reward_reserve: &AccountInfo<'a>,
+    reward_token_account_target: &AccountInfo<'a>,
+    user_stake_account: &AccountInfo<'a>,
+    viewer_wallet: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    stake_pool_owner: Option<&AccountInfo<'a>>,    
+    input: crate::instruction::UnstakeInput,
+    intent_nonce: Option<u64>,
+) -> ProgramResult {
+    let mut viewer_stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    viewer_stake_pool_state.initialized()?;
+
+    if let Some(stake_pool_owner) = stake_pool_owner {
+        stake_pool_owner.is_signer()?;
+        ensure_owner!(
+            stake_pool_owner,
+            viewer_stake_pool_state,
+            errors::Error::StakePoolOwnerMustOwnStake
+        );
+        for target in [token_account_target, reward_token_account_target] {
+            let borrow = target.try_borrow_data().unwrap();
+            let token_account_data = spl_token::state::Account::unpack(&borrow)?;
+            let associated_token_address = spl_associated_token_account::get_associated_token_address(&user_wallet.pubkey(), &token_account_data.mint);
+            if associated_token_address != target.pubkey() {
+                return errors::Error::AdminCanUnstakeOnlyToUserWalletAssosiatedTokenAddress.into();
+            }
+        }
+    }
+    else if intent_nonce.is_none() {
+        user_wallet.is_signer()?;
+    }
+
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    ensure_eq!(
+        program_id,
+        user_stake_account.owner,
+        errors::Error::StakeUserAccountMustBeOwnedByThisContract
+    );    
+
+    let mut user_stake_account_state = user_stake_account.deserialize::<ViewerStake>()?;
+    user_stake_account_state.initialized()?;
+
+    ensure_derived!(
+        user_stake_account_state.owner, 
+        user_wallet,
+        errors::Error::UserWalletMustBeOwnerOfViewerStakeAccount
+    );
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    if user_stake_account_state.staked_until > clock.unix_timestamp
+        && !viewer_stake_pool_state.emergency
+    {
+        return errors::Error::UnstakeCanBeDoneOnlyAfterStakeTimeLapsed.into();
+    }
+
+    let (stake_authority_pubkey, bump_seed, token_account_stake_source_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
+
+
+    ensure_derived!(
+        token_account_stake_source_pubkey, 
+        token_account_stake_source,
+        errors::Error::StakePoolTokenAccountMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        derive_reward_reserve(&stake_authority_pubkey)?,
+        reward_reserve,
+        errors::Error::RewardReserveMustBeDerivedFromPool
+    );
+
+    let (stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority_pubkey,
+        &user_wallet.pubkey(),
+        input.position,
+        program_id,
+    )?;
+
+    is_derived(stake_account_pubkey, user_stake_account)?;
+
+    let unstaked = input.amount.unwrap_or(user_stake_account_state.amount);
+    ensure!(
+        unstaked <= user_stake_account_state.amount,
+        errors::Error::UnstakeAmountExceedsStake
+    );
+    // in emergency only principal is returned
+    let reward = if viewer_stake_pool_state.emergency {
+        0
+    } else {
+        user_stake_account_state
+            .reward_for(unstaked)
+            .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?
+    };
+
+    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+
+    // transfer previously staked amount to user token account
+    invoke::spl_token_transfer_signed(
+        spl_token,
+        token_account_stake_source,
+        token_account_target,
+        stake_authority,
+        unstaked,
+        &authority_signature,
+    )?;
+
+    // reward is paid from reserve only, so principal of other stakes is never used
+    if reward > 0 {
+        ensure_eq!(
+            &spl_token::id(),
+            reward_reserve.owner,
+            errors::Error::UnstakeRewardPoolUnderfunded
+        );
+        let reward_reserve_state =
+            spl_token::state::Account::unpack(&reward_reserve.try_borrow_data()?)?;
+        ensure!(
+            reward_reserve_state.amount >= reward,
+            errors::Error::UnstakeRewardPoolUnderfunded
+        );
+        invoke::spl_token_transfer_signed(
+            spl_token,
+            reward_reserve,
+            reward_token_account_target,
+            stake_authority,
+            reward,
+            &authority_signature,
+        )?;
     }
-    else {
-        stake_pool_owner.unwrap()
+
+    let user_stake_account_state_before = user_stake_account_state.clone();
+    user_stake_account_state
+        .withdraw(unstaked)
+        .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?;
+    viewer_stake_pool_state
+        .account_stake(&user_stake_account_state_before, &mut user_stake_account_state)
+        .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?;
+    account_viewer_wallet_stake(
+        program_id,
+        stake_pool,
+        viewer_wallet,
+        user_wallet,
+        &user_stake_account_state_before,
+        &user_stake_account_state,
+        intent_nonce,
+    )?;
+    viewer_stake_pool_state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    let rank = if user_stake_account_state.amount == 0 {
+        burn_account(user_stake_account, fee_payer);
+        None
+    } else {
+        // rest keeps terms it was staked under
+        user_stake_account_state
+            .serialize_const(&mut *user_stake_account.try_borrow_mut_data()?)?;
+        viewer_stake_pool_state.rank_of(&user_stake_account_state, clock.unix_timestamp)
     };
+    msg!("Rank {:?}", rank);
+    set_return_data(&rank.try_to_vec()?);
 
-    invoke::spl_token_transfer(
+    Ok(())
+}
+
+fn get_rank<'a>(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    viewer_stake_accounts: std::iter::Skip<std::slice::Iter<AccountInfo<'a>>>,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    let (stake_authority_pubkey, _, _) = derive_token_account(stake_pool, program_id)?;
+
+    let mut positions = Vec::<ViewerStake>::new();
+    for viewer_stake_account in viewer_stake_accounts {
+        ensure_eq!(
+            program_id,
+            viewer_stake_account.owner,
+            errors::Error::StakeUserAccountMustBeOwnedByThisContract
+        );
+        let viewer_stake_state = viewer_stake_account.deserialize::<ViewerStake>()?;
+        viewer_stake_state.initialized()?;
+
+        let (viewer_stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey_index(
+            &stake_authority_pubkey,
+            &viewer_stake_state.owner,
+            viewer_stake_state.position,
+            program_id,
+        )?;
+        ensure_derived!(
+            viewer_stake_account_pubkey,
+            viewer_stake_account,
+            errors::Error::StakeUserMustBeDerivedFromUserToken
+        );
+        ensure!(
+            positions.iter().all(|x| x.owner == viewer_stake_state.owner),
+            errors::Error::GetRankPositionsMustBeOfSingleWallet
+        );
+        ensure!(
+            positions.iter().all(|x| x.position != viewer_stake_state.position),
+            errors::Error::GetRankPositionsMustBeUnique
+        );
+        positions.push(viewer_stake_state);
+    }
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    let rank = stake_pool_state.rank_of_positions(&positions, clock.unix_timestamp);
+    msg!("Rank {:?}", rank);
+    set_return_data(&rank.try_to_vec()?);
+    Ok(())
+}
+
+fn update_ranks(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    stake_pool_owner: &AccountInfo,
+    input: &crate::instruction::UpdateRanksInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+    ensure!(
+        ViewerStakePool::ranks_ordered(&input.ranks),
+        errors::Error::RanksMustBeOrderedByTimeAndAmount
+    );
+
+    // existing stakes keep `reward_multiplier` they staked under
+    state.ranks = input.ranks;
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn set_pool_mode(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    stake_pool_owner: &AccountInfo,
+    input: &crate::instruction::SetPoolModeInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+
+    state.paused = input.paused;
+    state.emergency = input.emergency;
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn set_stake_limits(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    stake_pool_owner: &AccountInfo,
+    input: &crate::instruction::SetStakeLimitsInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+
+    state.min_stake = input.min_stake;
+    state.max_stake_per_wallet = input.max_stake_per_wallet;
+    state.capacity = input.capacity;
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn propose_owner(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    stake_pool_owner: &AccountInfo,
+    input: &crate::instruction::ProposeOwnerInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+
+    state.pending_owner = input.pending_owner;
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn accept_owner(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    pending_owner: &AccountInfo,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.initialized()?;
+    pending_owner.is_signer()?;
+    ensure!(
+        state.pending_owner != Pubkey::default() && state.pending_owner == pending_owner.pubkey(),
+        errors::Error::OnlyPendingOwnerCanAcceptOwnership
+    );
+
+    state.owner = state.pending_owner;
+    state.pending_owner = Pubkey::default();
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn set_viewer_kyc<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    viewer_wallet: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    stake_pool_owner: &AccountInfo<'a>,
+    input: &crate::instruction::SetViewerKycInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        stake_pool_state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+
+    // KYC before first stake creates wallet, positions staked later read it
+    let mut viewer_wallet_state = load_or_create_viewer_wallet(
+        program_id,
+        system_program,
+        sysvar_rent,
+        fee_payer,
+        stake_pool,
+        stake_authority,
+        viewer_wallet,
+        user_wallet,
+    )?;
+    viewer_wallet_state.kyc_verified = input.kyc_verified;
+    viewer_wallet_state.serialize_const(&mut *viewer_wallet.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn set_early_unstake_penalty(
+    program_id: &ProgramPubkey,
+    stake_pool: &AccountInfo,
+    stake_pool_owner: &AccountInfo,
+    token_account_stake_pool: &AccountInfo,
+    treasury: &AccountInfo,
+    input: &crate::instruction::SetEarlyUnstakePenaltyInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let mut state = stake_pool.deserialize::<ViewerStakePool>()?;
+    state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+    ensure!(
+        input.penalty_bps <= ViewerStakePool::BPS,
+        errors::Error::EarlyUnstakePenaltyMustNotExceedWholeAmount
+    );
+
+    let (_, _, token_account_stake_pool_pubkey) = derive_token_account(stake_pool, program_id)?;
+    ensure_derived!(
+        token_account_stake_pool_pubkey,
+        token_account_stake_pool,
+        errors::Error::StakePoolTokenAccountMustBeDerivedFromPool
+    );
+    let token_account_stake_pool_state =
+        spl_token::state::Account::unpack(&token_account_stake_pool.try_borrow_data()?)?;
+    let treasury_state = spl_token::state::Account::unpack(&treasury.try_borrow_data()?)?;
+    ensure_eq!(
+        token_account_stake_pool_state.mint,
+        treasury_state.mint,
+        errors::Error::EarlyUnstakeTreasuryMustHoldPoolMint
+    );
+
+    state.early_unstake_penalty_bps = input.penalty_bps;
+    state.treasury = treasury.pubkey();
+    state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn early_unstake<'a>(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    token_account_target: &AccountInfo<'a>,
+    token_account_stake_source: &AccountInfo<'a>,
+    user_stake_account: &AccountInfo<'a>,
+    viewer_wallet: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    treasury: &AccountInfo<'a>,
+    input: crate::instruction::UnstakeInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    ensure_eq!(
+        program_id,
+        user_stake_account.owner,
+        errors::Error::StakeUserAccountMustBeOwnedByThisContract
+    );
+    let mut viewer_stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    viewer_stake_pool_state.initialized()?;
+    ensure!(
+        viewer_stake_pool_state.treasury != Pubkey::default(),
+        errors::Error::EarlyUnstakeIsNotEnabled
+    );
+    ensure_derived!(
+        viewer_stake_pool_state.treasury,
+        treasury,
+        errors::Error::EarlyUnstakeTreasuryMustBeSetOnPool
+    );
+    user_wallet.is_signer()?;
+
+    let mut user_stake_account_state = user_stake_account.deserialize::<ViewerStake>()?;
+    user_stake_account_state.initialized()?;
+    ensure_derived!(
+        user_stake_account_state.owner,
+        user_wallet,
+        errors::Error::UserWalletMustBeOwnerOfViewerStakeAccount
+    );
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    ensure!(
+        user_stake_account_state.staked_until > clock.unix_timestamp,
+        errors::Error::EarlyUnstakeCanBeDoneOnlyBeforeStakeTimeLapsed
+    );
+
+    let (stake_authority_pubkey, bump_seed, token_account_stake_source_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
+    ensure_derived!(
+        token_account_stake_source_pubkey,
+        token_account_stake_source,
+        errors::Error::StakePoolTokenAccountMustBeDerivedFromPool
+    );
+    let (stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority_pubkey,
+        &user_wallet.pubkey(),
+        input.position,
+        program_id,
+    )?;
+    is_derived(stake_account_pubkey, user_stake_account)?;
+
+    let unstaked = input.amount.unwrap_or(user_stake_account_state.amount);
+    ensure!(
+        unstaked <= user_stake_account_state.amount,
+        errors::Error::UnstakeAmountExceedsStake
+    );
+    let penalty = user_stake_account_state
+        .early_unstake_penalty(unstaked)
+        .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?;
+
+    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+
+    // no reward is paid, penalty is withheld into treasury
+    invoke::spl_token_transfer_signed(
         spl_token,
-        token_account_source,
-        token_account_stake_target,
-        signer,
-        input.amount,
+        token_account_stake_source,
+        token_account_target,
+        stake_authority,
+        unstaked - penalty,
+        &authority_signature,
+    )?;
+    if penalty > 0 {
+        invoke::spl_token_transfer_signed(
+            spl_token,
+            token_account_stake_source,
+            treasury,
+            stake_authority,
+            penalty,
+            &authority_signature,
+        )?;
+    }
+
+    // reward of unstaked part is not owed anymore
+    let user_stake_account_state_before = user_stake_account_state.clone();
+    user_stake_account_state
+        .withdraw(unstaked)
+        .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?;
+    viewer_stake_pool_state
+        .account_stake(&user_stake_account_state_before, &mut user_stake_account_state)
+        .ok_or::<ProgramError>(errors::Error::UnstakeOverflow.into())?;
+    account_viewer_wallet_stake(
+        program_id,
+        stake_pool,
+        viewer_wallet,
+        user_wallet,
+        &user_stake_account_state_before,
+        &user_stake_account_state,
+        None,
     )?;
-    stake_user_account_state.serialize_const(&mut *viewer_stake_account.try_borrow_mut_data()?)?;
+    viewer_stake_pool_state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+    let rank = if user_stake_account_state.amount == 0 {
+        burn_account(user_stake_account, fee_payer);
+        None
+    } else {
+        user_stake_account_state
+            .serialize_const(&mut *user_stake_account.try_borrow_mut_data()?)?;
+        viewer_stake_pool_state.rank_of(&user_stake_account_state, clock.unix_timestamp)
+    };
+    msg!("Rank {:?}", rank);
+    set_return_data(&rank.try_to_vec()?);
+
     Ok(())
 }
 
-fn unstake<'a>(
+#[allow(clippy::too_many_arguments)]
+fn add_to_stake<'a>(
     program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
     sysvar_clock: &AccountInfo<'a>,
     spl_token: &AccountInfo<'a>,
     fee_payer: &AccountInfo<'a>,
     stake_pool: &AccountInfo<'a>,
     stake_authority: &AccountInfo<'a>,
-    token_account_target: &AccountInfo<'a>,
-    token_account_stake_source: &AccountInfo<'a>,
-    user_stake_account: &AccountInfo<'a>,
+    token_account_source: &AccountInfo<'a>,
+    token_account_stake_target: &AccountInfo<'a>,
+    viewer_stake_account: &AccountInfo<'a>,
+    viewer_wallet: &AccountInfo<'a>,
     user_wallet: &AccountInfo<'a>,
-    stake_pool_owner: Option<&AccountInfo<'a>>,    
+    source_authority: &AccountInfo<'a>,
+    input: crate::instruction::AddToStakeInput,
 ) -> ProgramResult {
-    let viewer_stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
-    viewer_stake_pool_state.initialized()?;
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    ensure_eq!(
+        program_id,
+        viewer_stake_account.owner,
+        errors::Error::StakeUserAccountMustBeOwnedByThisContract
+    );
+    let mut stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    stake_pool_state.accepts_deposits()?;
+    // anybody can top up as it never resets lock
+    source_authority.is_signer()?;
 
-    if let Some(stake_pool_owner) = stake_pool_owner {
-        stake_pool_owner.is_signer()?;
-        ensure_owner!(
-            stake_pool_owner,
-            viewer_stake_pool_state,
-            errors::Error::StakePoolOwnerMustOwnStake
-        );
-        let borrow = token_account_target.try_borrow_data().unwrap();
-        let token_account_data = spl_token::state::Account::unpack(&borrow)?;
-        let associated_token_address = spl_associated_token_account::get_associated_token_address(&user_wallet.pubkey(), &token_account_data.mint);
-        if associated_token_address != token_account_target.pubkey() {
-            return errors::Error::AdminCanUnstakeOnlyToUserWalletAssosiatedTokenAddress.into();
-        }
-    }
-    else {
-        user_wallet.is_signer()?;
+    let (stake_authority_pubkey, _, token_account_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
+    let (viewer_stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority_pubkey,
+        &user_wallet.pubkey(),
+        input.position,
+        program_id,
+    )?;
+    ensure_derived!(
+        token_account_pubkey,
+        token_account_stake_target,
+        errors::Error::StakeTokenAccountMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        viewer_stake_account_pubkey,
+        viewer_stake_account,
+        errors::Error::StakeUserMustBeDerivedFromUserToken
+    );
+
+    let mut viewer_stake_state = viewer_stake_account.deserialize::<ViewerStake>()?;
+    viewer_stake_state.initialized()?;
+    ensure_owner!(
+        user_wallet,
+        viewer_stake_state,
+        errors::Error::UserWalletMustBeOwnerOfViewerStakeAccount
+    );
+    let mut viewer_wallet_state = load_or_create_viewer_wallet(
+        program_id,
+        system_program,
+        sysvar_rent,
+        fee_payer,
+        stake_pool,
+        stake_authority,
+        viewer_wallet,
+        user_wallet,
+    )?;
+    ensure!(
+        !stake_pool_state.kyc_required || viewer_wallet_state.kyc_verified,
+        errors::Error::StakeViewerMustBeKycVerified
+    );
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    // added amount is locked for rest of lock only, so it must be long enough as any new stake
+    let locked_for = viewer_stake_state.staked_until - clock.unix_timestamp;
+    ensure!(
+        viewer_stake_state.amount > 0 && locked_for > 0,
+        errors::Error::TopUpRequiresActiveLock
+    );
+    if locked_for < stake_pool_state.ranks[0].minimal_staking_time {
+        return errors::Error::StakeStakingTimeMustBeMoreThanMinimal.into();
     }
+    let amount = viewer_stake_state
+        .amount
+        .checked_add(input.amount)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    // rank reached for rest of lock applies to added amount only
+    let reward_multiplier = stake_pool_state.reward_multiplier(amount, locked_for);
+    let viewer_stake_state_before = viewer_stake_state.clone();
+    viewer_stake_state
+        .top_up(input.amount, clock.unix_timestamp, reward_multiplier)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    let rank = stake_pool_state.rank_of(&viewer_stake_state, clock.unix_timestamp);
+
+    invoke::spl_token_transfer(
+        spl_token,
+        token_account_source,
+        token_account_stake_target,
+        source_authority,
+        input.amount,
+    )?;
+    stake_pool_state
+        .account_stake(&viewer_stake_state_before, &mut viewer_stake_state)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    viewer_wallet_state
+        .account_stake(&viewer_stake_state_before, &viewer_stake_state)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    stake_pool_state.check_stake_limits(&viewer_stake_state, &viewer_wallet_state)?;
+    viewer_stake_state.serialize_const(&mut *viewer_stake_account.try_borrow_mut_data()?)?;
+    viewer_wallet_state.serialize_const(&mut *viewer_wallet.try_borrow_mut_data()?)?;
+    stake_pool_state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+
+    msg!("Rank {:?}", rank);
+    set_return_data(&rank.try_to_vec()?);
+    Ok(())
+}
 
+fn extend_lock(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo,
+    stake_pool: &AccountInfo,
+    viewer_stake_account: &AccountInfo,
+    user_wallet: &AccountInfo,
+    input: crate::instruction::ExtendLockInput,
+) -> ProgramResult {
     ensure_eq!(
         program_id,
         stake_pool.owner,
@@ -381,55 +1741,343 @@ fn unstake<'a>(
     );
     ensure_eq!(
         program_id,
-        user_stake_account.owner,
+        viewer_stake_account.owner,
         errors::Error::StakeUserAccountMustBeOwnedByThisContract
-    );    
-
-    let user_stake_account_state = user_stake_account.deserialize::<ViewerStake>()?;
-    user_stake_account_state.initialized()?;
+    );
+    let mut stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    user_wallet.is_signer()?;
 
+    let (stake_authority_pubkey, _, _) = derive_token_account(stake_pool, program_id)?;
+    let (viewer_stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority_pubkey,
+        &user_wallet.pubkey(),
+        input.position,
+        program_id,
+    )?;
     ensure_derived!(
-        user_stake_account_state.owner, 
+        viewer_stake_account_pubkey,
+        viewer_stake_account,
+        errors::Error::StakeUserMustBeDerivedFromUserToken
+    );
+
+    let mut viewer_stake_state = viewer_stake_account.deserialize::<ViewerStake>()?;
+    viewer_stake_state.initialized()?;
+    ensure_owner!(
         user_wallet,
+        viewer_stake_state,
         errors::Error::UserWalletMustBeOwnerOfViewerStakeAccount
     );
 
     let clock = Clock::from_account_info(sysvar_clock)?;
-    if user_stake_account_state.staked_until > clock.unix_timestamp {
-        return errors::Error::UnstakeCanBeDoneOnlyAfterStakeTimeLapsed.into();
+    // lapsed lock cannot be extended back in time, it should be staked again
+    ensure!(
+        viewer_stake_state.staked_until > clock.unix_timestamp,
+        errors::Error::ExtendLockCanBeDoneOnlyBeforeStakeTimeLapsed
+    );
+    if input.duration < viewer_stake_state.duration() {
+        return errors::Error::StakeStakingTimeMustBeMoreThanPrevious.into();
     }
 
-    let (stake_authority_pubkey, bump_seed, token_account_stake_source_pubkey) =
-        derive_token_account(stake_pool, program_id)?;
+    let viewer_stake_state_before = viewer_stake_state.clone();
+    viewer_stake_state.staked_until = viewer_stake_state.staked_at + input.duration;
+    // rank reached by longer lock applies to whole stake
+    viewer_stake_state.reward_multiplier = viewer_stake_state
+        .reward_multiplier
+        .max(stake_pool_state.reward_multiplier(viewer_stake_state.amount, input.duration));
+    stake_pool_state
+        .account_stake(&viewer_stake_state_before, &mut viewer_stake_state)
+        .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+    viewer_stake_state.serialize_const(&mut *viewer_stake_account.try_borrow_mut_data()?)?;
+    stake_pool_state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
+
+    let rank = stake_pool_state.rank_of(&viewer_stake_state, clock.unix_timestamp);
+    msg!("Rank {:?}", rank);
+    set_return_data(&rank.try_to_vec()?);
+    Ok(())
+}
 
+#[allow(clippy::too_many_arguments)]
+fn fund_rewards<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    token_account_stake_pool: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    reward_reserve: &AccountInfo<'a>,
+    token_account_source: &AccountInfo<'a>,
+    source_authority: &AccountInfo<'a>,
+    input: &crate::instruction::RewardsInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    source_authority.is_signer()?;
 
+    let (stake_authority_pubkey, bump_seed, token_account_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
     ensure_derived!(
-        token_account_stake_source_pubkey, 
-        token_account_stake_source,
+        stake_authority_pubkey,
+        stake_authority,
+        errors::Error::StakeAuthorityMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        token_account_pubkey,
+        token_account_stake_pool,
         errors::Error::StakePoolTokenAccountMustBeDerivedFromPool
     );
+    ensure_derived!(
+        derive_reward_reserve(&stake_authority_pubkey)?,
+        reward_reserve,
+        errors::Error::RewardReserveMustBeDerivedFromPool
+    );
 
-    let (stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey(
-        &stake_authority_pubkey,
-        &user_wallet.pubkey(),
-        program_id,
+    if reward_reserve.data_is_empty() {
+        fee_payer.is_signer()?;
+        let reward_mint = if stake_pool_state.reward_mint == Pubkey::default() {
+            spl_token::state::Account::unpack(&token_account_stake_pool.try_borrow_data()?)?.mint
+        } else {
+            stake_pool_state.reward_mint
+        };
+        ensure_derived!(
+            reward_mint,
+            mint,
+            errors::Error::RewardReserveMustHoldPoolMint
+        );
+
+        let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+        let rent_state = Rent::from_account_info(sysvar_rent)?;
+        let lamports = rent_state.minimum_balance(spl_token::state::Account::LEN);
+        invoke::create_account_with_seed_signed(
+            system_program,
+            fee_payer,
+            reward_reserve,
+            stake_authority,
+            "ViewerStakePool::reward_reserve",
+            lamports,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+            &authority_signature,
+        )?;
+        invoke::initialize_token_account_signed(
+            reward_reserve,
+            mint,
+            stake_authority,
+            sysvar_rent,
+            &authority_signature,
+        )?;
+    }
+
+    invoke::spl_token_transfer(
+        spl_token,
+        token_account_source,
+        reward_reserve,
+        source_authority,
+        input.amount,
     )?;
+    Ok(())
+}
 
-    is_derived(stake_account_pubkey, user_stake_account)?;
+#[allow(clippy::too_many_arguments)]
+fn withdraw_unallocated_rewards<'a>(
+    program_id: &ProgramPubkey,
+    spl_token: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    reward_reserve: &AccountInfo<'a>,
+    token_account_target: &AccountInfo<'a>,
+    stake_pool_owner: &AccountInfo<'a>,
+    input: &crate::instruction::RewardsInput,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        stake_pool_state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
 
-    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+    let (stake_authority_pubkey, bump_seed, _) = derive_token_account(stake_pool, program_id)?;
+    ensure_derived!(
+        stake_authority_pubkey,
+        stake_authority,
+        errors::Error::StakeAuthorityMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        derive_reward_reserve(&stake_authority_pubkey)?,
+        reward_reserve,
+        errors::Error::RewardReserveMustBeDerivedFromPool
+    );
 
-    // transfer previously staked amount to user token account
+    let reward_reserve_state =
+        spl_token::state::Account::unpack(&reward_reserve.try_borrow_data()?)?;
+    // rewards owed to stakes stay in reserve
+    ensure!(
+        input.amount <= stake_pool_state.unallocated_rewards(reward_reserve_state.amount),
+        errors::Error::WithdrawExceedsUnallocatedRewards
+    );
+
+    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
     invoke::spl_token_transfer_signed(
         spl_token,
-        token_account_stake_source,
+        reward_reserve,
         token_account_target,
         stake_authority,
-        user_stake_account_state.amount,
+        input.amount,
+        &authority_signature,
+    )?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn close_pool<'a>(
+    program_id: &ProgramPubkey,
+    spl_token: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    stake_authority: &AccountInfo<'a>,
+    token_account_stake_pool: &AccountInfo<'a>,
+    reward_reserve: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    stake_pool_owner: &AccountInfo<'a>,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    let stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
+    stake_pool_state.initialized()?;
+    stake_pool_owner.is_signer()?;
+    ensure_owner!(
+        stake_pool_owner,
+        stake_pool_state,
+        errors::Error::StakePoolOwnerMustOwnStake
+    );
+
+    let (stake_authority_pubkey, bump_seed, token_account_pubkey) =
+        derive_token_account(stake_pool, program_id)?;
+    ensure_derived!(
+        stake_authority_pubkey,
+        stake_authority,
+        errors::Error::StakeAuthorityMustBeDerivedFromStake
+    );
+    ensure_derived!(
+        token_account_pubkey,
+        token_account_stake_pool,
+        errors::Error::StakePoolTokenAccountMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        derive_reward_reserve(&stake_authority_pubkey)?,
+        reward_reserve,
+        errors::Error::RewardReserveMustBeDerivedFromPool
+    );
+
+    // stakes not counted in totals still hold principal in token account
+    let token_account_stake_pool_state =
+        spl_token::state::Account::unpack(&token_account_stake_pool.try_borrow_data()?)?;
+    ensure!(
+        stake_pool_state.total_staked == 0
+            && stake_pool_state.staker_count == 0
+            && token_account_stake_pool_state.amount == 0,
+        errors::Error::ClosePoolMustHaveNoStakes
+    );
+
+    let authority_signature = ProgramPubkeySignature::new(stake_pool, bump_seed);
+    invoke::close_token_account_signed(
+        spl_token,
+        token_account_stake_pool,
+        destination,
+        stake_authority,
         &authority_signature,
     )?;
+    if !reward_reserve.data_is_empty() {
+        let reward_reserve_state =
+            spl_token::state::Account::unpack(&reward_reserve.try_borrow_data()?)?;
+        ensure!(
+            reward_reserve_state.amount == 0,
+            errors::Error::ClosePoolRewardReserveMustBeWithdrawn
+        );
+        invoke::close_token_account_signed(
+            spl_token,
+            reward_reserve,
+            destination,
+            stake_authority,
+            &authority_signature,
+        )?;
+    }
+    burn_account(stake_pool, destination);
+    Ok(())
+}
+
+fn migrate<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    stake_pool: &AccountInfo<'a>,
+    viewer_stake_accounts: std::iter::Skip<std::slice::Iter<AccountInfo<'a>>>,
+) -> ProgramResult {
+    ensure_eq!(
+        program_id,
+        stake_pool.owner,
+        errors::Error::StakePoolMustBeOwnedByThisContract
+    );
+    fee_payer.is_signer()?;
+    let rent = Rent::from_account_info(sysvar_rent)?;
+    migrate_state::<ViewerStakePool>(system_program, fee_payer, stake_pool, &rent)?;
+    let mut stake_pool_state = stake_pool.deserialize::<ViewerStakePool>()?;
 
-    burn_account(user_stake_account, fee_payer);
+    let (stake_authority_pubkey, _, _) = derive_token_account(stake_pool, program_id)?;
+    for viewer_stake_account in viewer_stake_accounts {
+        ensure_eq!(
+            program_id,
+            viewer_stake_account.owner,
+            errors::Error::StakeUserAccountMustBeOwnedByThisContract
+        );
+        // stakes of first version were not counted
+        let counted = StateVersion::read(&viewer_stake_account.try_borrow_data()?)?
+            != StateVersion::V1;
+        if let Some(mut viewer_stake_state) = migrate_state::<ViewerStake>(
+            system_program,
+            fee_payer,
+            viewer_stake_account,
+            &rent,
+        )? {
+            let (viewer_stake_account_pubkey, _) = Pubkey::create_with_seed_for_pubkey_index(
+                &stake_authority_pubkey,
+                &viewer_stake_state.owner,
+                viewer_stake_state.position,
+                program_id,
+            )?;
+            ensure_derived!(
+                viewer_stake_account_pubkey,
+                viewer_stake_account,
+                errors::Error::StakeUserMustBeDerivedFromUserToken
+            );
+            if !counted {
+                stake_pool_state
+                    .account_stake(&ViewerStake::default(), &mut viewer_stake_state)
+                    .ok_or::<ProgramError>(errors::Error::StakeOverflow.into())?;
+                viewer_stake_state
+                    .serialize_const(&mut *viewer_stake_account.try_borrow_mut_data()?)?;
+            }
+        }
+    }
 
+    stake_pool_state.serialize_const(&mut *stake_pool.try_borrow_mut_data()?)?;
     Ok(())
 }
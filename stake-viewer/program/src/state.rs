@@ -1,8 +1,13 @@
 //! Program owned state
+use std::convert::TryFrom;
+
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
-use sator_sdk::state::StateVersion;
-use sator_sdk::types::{ApproximateSeconds, SignerPubkey, TokenAmount};
+use sator_sdk::state::{StateVersion, VersionedState};
+use sator_sdk::types::{
+    ApproximateSeconds, MintPubkey, ProgramPubkey, SignerPubkey, TokenAccountPubkey, TokenAmount,
+};
 use solana_program::clock::UnixTimestamp;
+use solana_program::pubkey::{Pubkey, PubkeyError};
 use solana_program::{entrypoint::ProgramResult, program_error::ProgramError};
 
 use crate::types::*;
@@ -16,10 +21,86 @@ pub struct ViewerStakePool {
     pub ranks: [Rank; 4],
     // can initialize state and change rules
     pub owner: SignerPubkey,
+    /// proposed by `owner` to take over the pool, default if none
+    pub pending_owner: Pubkey,
+    /// only viewers with [ViewerWallet::kyc_verified] can stake
+    pub kyc_required: bool,
+    /// part of stake withheld on early unstake, in [ViewerStakePool::BPS] units
+    pub early_unstake_penalty_bps: u16,
+    /// token account receiving early unstake penalties, default if early unstake is disabled
+    pub treasury: TokenAccountPubkey,
+    /// set by `owner`, no new tokens can be staked
+    pub paused: bool,
+    /// set by `owner`, stakers can unstake principal without reward regardless of lock
+    pub emergency: bool,
+    /// principal of all stakes held in `token_account`
+    pub total_staked: TokenAmount,
+    /// rewards owed to all stakes, `reward_reserve` tokens above it can be withdrawn by `owner`
+    pub allocated_rewards: TokenAmount,
+    /// count of stake positions with non zero amount
+    pub staker_count: u32,
+    /// count of stake positions with non zero amount by [ViewerStake::rank]
+    pub rank_staker_counts: [u32; 4],
+    /// mint of `reward_reserve`, default if rewards are paid in staked mint
+    pub reward_mint: MintPubkey,
+    /// minimal amount of [ViewerStake] after deposit
+    pub min_stake: TokenAmount,
+    /// maximal amount wallet can hold in all its [ViewerStake] positions, see [ViewerWallet::total_staked], zero if unlimited
+    pub max_stake_per_wallet: TokenAmount,
+    /// maximal `total_staked`, zero if unlimited
+    pub capacity: TokenAmount,
 }
-/// User stake account state
+/// [ViewerStakePool] layout of [StateVersion::V1]
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct ViewerStakePoolV1 {
+    pub version: StateVersion,
+    pub ranks: [RankV1; 4],
+    pub owner: SignerPubkey,
+}
+
+/// [ViewerStakePool] layout of [StateVersion::V2]
 #[repr(C)]
 #[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct ViewerStakePoolV2 {
+    pub version: StateVersion,
+    pub ranks: [Rank; 4],
+    pub owner: SignerPubkey,
+    pub pending_owner: Pubkey,
+    pub kyc_required: bool,
+    pub early_unstake_penalty_bps: u16,
+    pub treasury: TokenAccountPubkey,
+    pub paused: bool,
+    pub emergency: bool,
+    pub total_staked: TokenAmount,
+    pub allocated_rewards: TokenAmount,
+    pub staker_count: u32,
+    pub rank_staker_counts: [u32; 4],
+}
+
+/// [ViewerStakePool] layout of [StateVersion::V3]
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct ViewerStakePoolV3 {
+    pub version: StateVersion,
+    pub ranks: [Rank; 4],
+    pub owner: SignerPubkey,
+    pub pending_owner: Pubkey,
+    pub kyc_required: bool,
+    pub early_unstake_penalty_bps: u16,
+    pub treasury: TokenAccountPubkey,
+    pub paused: bool,
+    pub emergency: bool,
+    pub total_staked: TokenAmount,
+    pub allocated_rewards: TokenAmount,
+    pub staker_count: u32,
+    pub rank_staker_counts: [u32; 4],
+    pub reward_mint: MintPubkey,
+}
+
+/// User stake account state
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default, Clone)]
 pub struct ViewerStake {
     pub version: StateVersion,
     pub staked_at: UnixTimestamp,
@@ -27,10 +108,154 @@ pub struct ViewerStake {
     /// user owner of stake
     pub owner: SignerPubkey,
     pub amount: TokenAmount,
+    /// APY of rank reached when staked, in [Rank::ONE] units, kept until unstake even if pool ranks are updated
+    pub reward_multiplier: u64,
+    /// index of position of `owner` used to derive this account, 0 for first position
+    pub position: u8,
+    /// sum of top up amounts times seconds of lock passed before top up, excluded from reward
+    pub late_stake_seconds: u128,
+    /// rank reached by `amount` and lock at last change, counted in [ViewerStakePool::rank_staker_counts], [ViewerStake::NO_RANK] if none
+    pub rank: u8,
+    /// penalty of pool when staked, in [ViewerStakePool::BPS] units, withheld on early unstake even if pool penalty is changed
+    pub early_unstake_penalty_bps: u16,
+    /// reward earned under lock replaced by restake, paid on unstake with reward of current lock
+    pub carried_reward: TokenAmount,
 }
 
-impl ViewerStake {
+/// Wallet state kept by pool, derived from `stake_authority` and wallet, see [ViewerWallet::derive].
+/// Never closed, unlike [ViewerStake] positions which are closed on whole unstake.
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default, Clone)]
+pub struct ViewerWallet {
+    pub version: StateVersion,
+    /// wallet of viewer
+    pub owner: SignerPubkey,
+    /// set by pool `owner` only
+    pub kyc_verified: bool,
+    /// sum of amounts of positions of wallet, checked against [ViewerStakePool::max_stake_per_wallet]
+    pub total_staked: TokenAmount,
+    /// nonce next intent signed by `owner` off chain must have, incremented on each executed intent
+    pub nonce: u64,
+}
+
+/// [ViewerStake] layout of [StateVersion::V1]
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct ViewerStakeV1 {
+    pub version: StateVersion,
+    pub staked_at: UnixTimestamp,
+    pub staked_until: UnixTimestamp,
+    pub owner: SignerPubkey,
+    pub amount: TokenAmount,
+}
+
+impl ViewerStakePoolV1 {
+    pub const LEN: usize = 97;
+}
+
+impl ViewerStakePoolV2 {
+    pub const LEN: usize = 234;
+}
+
+impl ViewerStakePoolV3 {
+    pub const LEN: usize = 266;
+}
+
+impl ViewerStakeV1 {
     pub const LEN: usize = 57;
+}
+
+impl VersionedState for ViewerStakePool {
+    const VERSION: StateVersion = StateVersion::V4;
+    const LEN: usize = ViewerStakePool::LEN;
+
+    fn deserialize_versioned(data: &[u8]) -> Result<Self, ProgramError> {
+        match StateVersion::read(data)? {
+            StateVersion::Uninitialized => Err(ProgramError::UninitializedAccount),
+            StateVersion::V1 => {
+                let state = ViewerStakePoolV1::try_from_slice(data)?;
+                Ok(Self {
+                    version: Self::VERSION,
+                    ranks: state.ranks.map(Rank::from),
+                    owner: state.owner,
+                    ..Default::default()
+                })
+            }
+            StateVersion::V2 => {
+                let state = ViewerStakePoolV2::try_from_slice(data)?;
+                Ok(Self {
+                    version: Self::VERSION,
+                    ranks: state.ranks,
+                    owner: state.owner,
+                    pending_owner: state.pending_owner,
+                    kyc_required: state.kyc_required,
+                    early_unstake_penalty_bps: state.early_unstake_penalty_bps,
+                    treasury: state.treasury,
+                    paused: state.paused,
+                    emergency: state.emergency,
+                    total_staked: state.total_staked,
+                    allocated_rewards: state.allocated_rewards,
+                    staker_count: state.staker_count,
+                    rank_staker_counts: state.rank_staker_counts,
+                    ..Default::default()
+                })
+            }
+            StateVersion::V3 => {
+                let state = ViewerStakePoolV3::try_from_slice(data)?;
+                Ok(Self {
+                    version: Self::VERSION,
+                    ranks: state.ranks,
+                    owner: state.owner,
+                    pending_owner: state.pending_owner,
+                    kyc_required: state.kyc_required,
+                    early_unstake_penalty_bps: state.early_unstake_penalty_bps,
+                    treasury: state.treasury,
+                    paused: state.paused,
+                    emergency: state.emergency,
+                    total_staked: state.total_staked,
+                    allocated_rewards: state.allocated_rewards,
+                    staker_count: state.staker_count,
+                    rank_staker_counts: state.rank_staker_counts,
+                    reward_mint: state.reward_mint,
+                    ..Default::default()
+                })
+            }
+            StateVersion::V4 => Ok(Self::try_from_slice(data)?),
+        }
+    }
+}
+
+/// V1 stakes were derived for first position only and earned no reward
+impl VersionedState for ViewerStake {
+    const VERSION: StateVersion = StateVersion::V2;
+    const LEN: usize = ViewerStake::LEN;
+
+    fn deserialize_versioned(data: &[u8]) -> Result<Self, ProgramError> {
+        match StateVersion::read(data)? {
+            StateVersion::Uninitialized => Err(ProgramError::UninitializedAccount),
+            StateVersion::V1 => {
+                let state = ViewerStakeV1::try_from_slice(data)?;
+                Ok(Self {
+                    version: Self::VERSION,
+                    staked_at: state.staked_at,
+                    staked_until: state.staked_until,
+                    owner: state.owner,
+                    amount: state.amount,
+                    rank: Self::NO_RANK,
+                    ..Default::default()
+                })
+            }
+            StateVersion::V2 => Ok(Self::try_from_slice(data)?),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl ViewerStake {
+    pub const LEN: usize = 93;
+
+    /// [ViewerStake::rank] of stake not reaching any rank
+    pub const NO_RANK: u8 = u8::MAX;
 
     pub fn uninitialized(&self) -> ProgramResult {
         if self.version == StateVersion::Uninitialized {
@@ -51,10 +276,141 @@ impl ViewerStake {
     pub fn duration(&self) -> ApproximateSeconds {
         self.staked_until - self.staked_at
     }
+
+    /// `reward = stake time / year * amount * reward_multiplier + carried_reward`, `None` on overflow
+    pub fn reward(&self) -> Option<TokenAmount> {
+        self.reward_for(self.amount)
+    }
+
+    /// reward for unstaking `amount` out of stake, prorated from reward of whole stake
+    pub fn reward_for(&self, amount: TokenAmount) -> Option<TokenAmount> {
+        if self.amount == 0 {
+            return Some(0);
+        }
+        let stake_seconds = (self.amount as u128)
+            .checked_mul(self.duration().max(0) as u128)?
+            .checked_sub(self.late_stake_seconds)?;
+        let reward = (stake_seconds.checked_mul(self.reward_multiplier as u128)?
+            / (Rank::YEAR as u128 * Rank::ONE))
+            .checked_add(self.carried_reward as u128)?;
+        let reward = reward.checked_mul(amount as u128)? / self.amount as u128;
+        TokenAmount::try_from(reward).ok()
+    }
+
+    /// reward earned for part of lock passed at `now`, kept as [ViewerStake::carried_reward] on restake
+    pub fn reward_at(&self, now: UnixTimestamp) -> Option<TokenAmount> {
+        ViewerStake {
+            staked_until: self.staked_until.min(now),
+            ..self.clone()
+        }
+        .reward()
+    }
+
+    /// part of `amount` withheld on early unstake, `None` on overflow
+    pub fn early_unstake_penalty(&self, amount: TokenAmount) -> Option<TokenAmount> {
+        let penalty = (amount as u128).checked_mul(self.early_unstake_penalty_bps as u128)?
+            / ViewerStakePool::BPS as u128;
+        TokenAmount::try_from(penalty).ok()
+    }
+
+    /// adds `amount` keeping lock, so it earns reward only for rest of lock at `now`.
+    /// Added amount earns `reward_multiplier`, so stake gets average of both multipliers weighted by stake seconds.
+    pub fn top_up(
+        &mut self,
+        amount: TokenAmount,
+        now: UnixTimestamp,
+        reward_multiplier: u64,
+    ) -> Option<()> {
+        let duration = self.duration().max(0) as u128;
+        let stake_seconds = (self.amount as u128)
+            .checked_mul(duration)?
+            .checked_sub(self.late_stake_seconds)?;
+        let passed = (now.min(self.staked_until) - self.staked_at).max(0) as u128;
+        let late_stake_seconds = (amount as u128).checked_mul(passed)?;
+        let added_stake_seconds = (amount as u128).checked_mul(duration.checked_sub(passed)?)?;
+        let weighted = stake_seconds
+            .checked_mul(self.reward_multiplier as u128)?
+            .checked_add(added_stake_seconds.checked_mul(reward_multiplier as u128)?)?;
+        if let Some(weighted) = weighted.checked_div(stake_seconds.checked_add(added_stake_seconds)?) {
+            self.reward_multiplier = u64::try_from(weighted).ok()?;
+        }
+        self.late_stake_seconds = self.late_stake_seconds.checked_add(late_stake_seconds)?;
+        self.amount = self.amount.checked_add(amount)?;
+        Some(())
+    }
+
+    /// removes `amount` from stake, rest keeps its share of late stake seconds and carried reward
+    pub fn withdraw(&mut self, amount: TokenAmount) -> Option<()> {
+        if amount > self.amount {
+            return None;
+        }
+        if self.amount > 0 {
+            let withdrawn_late_stake_seconds =
+                self.late_stake_seconds.checked_mul(amount as u128)? / self.amount as u128;
+            self.late_stake_seconds -= withdrawn_late_stake_seconds;
+            let withdrawn_carried_reward =
+                (self.carried_reward as u128).checked_mul(amount as u128)? / self.amount as u128;
+            self.carried_reward -= withdrawn_carried_reward as TokenAmount;
+        }
+        self.amount -= amount;
+        Some(())
+    }
+}
+
+impl ViewerWallet {
+    pub const LEN: usize = 50;
+
+    /// Address of wallet state of `user_wallet` and its seed.
+    /// Seed ends with `_w`, so it never matches seed of [ViewerStake] position.
+    pub fn derive(
+        stake_authority: &Pubkey,
+        user_wallet: &Pubkey,
+        program_id: &ProgramPubkey,
+    ) -> Result<(Pubkey, String), PubkeyError> {
+        // 28 chars of 20 bytes base58 and suffix fit 32 chars limit
+        let seed = format!(
+            "{}_w",
+            bs58::encode(&user_wallet.to_bytes()[..20]).into_string()
+        );
+        let pubkey = Pubkey::create_with_seed(stake_authority, &seed, program_id)?;
+        Ok((pubkey, seed))
+    }
+
+    /// Error if not initialized
+    pub fn initialized(&self) -> ProgramResult {
+        if self.version != StateVersion::Uninitialized {
+            Ok(())
+        } else {
+            Err(ProgramError::UninitializedAccount)
+        }
+    }
+
+    /// increments `nonce` if intent has it, `None` otherwise
+    pub fn use_nonce(&mut self, nonce: u64) -> Option<()> {
+        if self.nonce != nonce {
+            return None;
+        }
+        self.nonce = nonce.checked_add(1)?;
+        Some(())
+    }
+
+    /// replaces `before` state of position with `after` in `total_staked`, `None` on overflow.
+    /// Positions staked before wallet was created are not counted, so total does not go below zero.
+    pub fn account_stake(&mut self, before: &ViewerStake, after: &ViewerStake) -> Option<()> {
+        self.total_staked = self
+            .total_staked
+            .saturating_sub(before.amount)
+            .checked_add(after.amount)?;
+        Some(())
+    }
 }
 
 impl ViewerStakePool {
-    pub const LEN: usize = 97;
+    pub const LEN: usize = 290;
+
+    /// whole amount in basis points
+    pub const BPS: u16 = 10_000;
+
     pub fn uninitialized(&self) -> ProgramResult {
         if self.version == StateVersion::Uninitialized {
             Ok(())
@@ -70,11 +426,128 @@ impl ViewerStakePool {
             Err(ProgramError::UninitializedAccount)
         }
     }
+
+    /// Error if pool does not accept new tokens
+    pub fn accepts_deposits(&self) -> ProgramResult {
+        if self.paused || self.emergency {
+            crate::errors::Error::StakePoolIsPaused.into()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Error if `stake` after deposit, already counted in `total_staked` and `wallet`, breaks pool limits
+    pub fn check_stake_limits(&self, stake: &ViewerStake, wallet: &ViewerWallet) -> ProgramResult {
+        if stake.amount < self.min_stake {
+            crate::errors::Error::StakeAmountBelowMinimum.into()
+        } else if self.max_stake_per_wallet > 0 && wallet.total_staked > self.max_stake_per_wallet
+        {
+            crate::errors::Error::StakeExceedsWalletCap.into()
+        } else if self.capacity > 0 && self.total_staked > self.capacity {
+            crate::errors::Error::StakeExceedsPoolCapacity.into()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// highest rank index reached by staking `amount` for `duration`
+    pub fn rank_for(&self, amount: TokenAmount, duration: ApproximateSeconds) -> Option<usize> {
+        self.ranks
+            .iter()
+            .rposition(|rank| duration >= rank.minimal_staking_time && amount >= rank.amount)
+    }
+
+    /// rank index held by `stake` at `now`, stake which lock lapsed holds only ranks without minimal staking time
+    pub fn rank_of(&self, stake: &ViewerStake, now: UnixTimestamp) -> Option<u8> {
+        self.rank_of_positions(std::slice::from_ref(stake), now)
+    }
+
+    /// rank index held by positions of single wallet at `now`.
+    /// Rank is reached if sum of amounts of positions staked for its minimal time reaches its amount.
+    pub fn rank_of_positions(&self, stakes: &[ViewerStake], now: UnixTimestamp) -> Option<u8> {
+        let duration = |stake: &ViewerStake| {
+            if now < stake.staked_until {
+                stake.duration()
+            } else {
+                0
+            }
+        };
+        self.ranks
+            .iter()
+            .rposition(|rank| {
+                let mut qualified = stakes
+                    .iter()
+                    .filter(|stake| duration(stake) >= rank.minimal_staking_time)
+                    .peekable();
+                qualified.peek().is_some()
+                    && qualified.fold(0u128, |sum, stake| sum + stake.amount as u128)
+                        >= rank.amount as u128
+            })
+            .map(|rank| rank as u8)
+    }
+
+    /// APY of highest rank reached by staking `amount` for `duration`, zero if no rank reached
+    pub fn reward_multiplier(&self, amount: TokenAmount, duration: ApproximateSeconds) -> u64 {
+        self.rank_for(amount, duration)
+            .map_or(0, |rank| self.ranks[rank].reward_multiplier)
+    }
+
+    /// replaces `before` state of stake with `after` in pool totals and statistics, sets [ViewerStake::rank] of `after`, `None` on overflow.
+    /// Stakes made before totals were tracked are not counted, so totals do not go below zero.
+    pub fn account_stake(&mut self, before: &ViewerStake, after: &mut ViewerStake) -> Option<()> {
+        self.total_staked = self
+            .total_staked
+            .saturating_sub(before.amount)
+            .checked_add(after.amount)?;
+        self.allocated_rewards = self
+            .allocated_rewards
+            .saturating_sub(before.reward()?)
+            .checked_add(after.reward()?)?;
+
+        after.rank = self
+            .rank_for(after.amount, after.duration())
+            .map_or(ViewerStake::NO_RANK, |rank| rank as u8);
+        if before.amount > 0 {
+            self.staker_count = self.staker_count.saturating_sub(1);
+            if let Some(count) = self.rank_staker_counts.get_mut(before.rank as usize) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        if after.amount > 0 {
+            self.staker_count = self.staker_count.checked_add(1)?;
+            if let Some(count) = self.rank_staker_counts.get_mut(after.rank as usize) {
+                *count = count.checked_add(1)?;
+            }
+        }
+        Some(())
+    }
+
+    /// `reward_reserve` tokens not owed to stakes
+    pub fn unallocated_rewards(&self, reward_reserve: TokenAmount) -> TokenAmount {
+        reward_reserve.saturating_sub(self.allocated_rewards)
+    }
+
+    /// each next rank requires not less time and amount than previous
+    pub fn ranks_ordered(ranks: &[Rank]) -> bool {
+        ranks.windows(2).all(|pair| {
+            pair[0].minimal_staking_time <= pair[1].minimal_staking_time
+                && pair[0].amount <= pair[1].amount
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::state::ViewerStake;
+    use crate::{
+        state::{
+            ViewerStake, ViewerStakePoolV1, ViewerStakePoolV2, ViewerStakePoolV3, ViewerStakeV1,
+            ViewerWallet,
+        },
+        types::{Rank, RankV1},
+    };
+    use sator_sdk::program::PubkeyPatterns;
+    use sator_sdk::state::{StateVersion, VersionedState};
+    use solana_program::pubkey::Pubkey;
 
     use super::ViewerStakePool;
     use borsh::*;
@@ -85,5 +558,457 @@ mod tests {
         assert_eq!(data.len(), ViewerStakePool::LEN);
         let data = ViewerStake::default().try_to_vec().unwrap();
         assert_eq!(data.len(), ViewerStake::LEN);
+        let data = ViewerStakePoolV1::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), ViewerStakePoolV1::LEN);
+        let data = ViewerStakePoolV2::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), ViewerStakePoolV2::LEN);
+        let data = ViewerStakePoolV3::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), ViewerStakePoolV3::LEN);
+        let data = ViewerStakeV1::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), ViewerStakeV1::LEN);
+        let data = ViewerWallet::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), ViewerWallet::LEN);
+    }
+
+    #[test]
+    fn use_nonce() {
+        let mut wallet = ViewerWallet::default();
+        assert!(wallet.use_nonce(1).is_none());
+        wallet.use_nonce(0).unwrap();
+        assert!(wallet.use_nonce(0).is_none(), "nonce cannot be replayed");
+        wallet.use_nonce(1).unwrap();
+        assert_eq!(wallet.nonce, 2);
+    }
+
+    #[test]
+    fn derive_viewer_wallet() {
+        let stake_authority = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let user_wallet = Pubkey::new_from_array([u8::MAX; 32]);
+        let (wallet, seed) =
+            ViewerWallet::derive(&stake_authority, &user_wallet, &program_id).unwrap();
+        assert!(seed.len() <= solana_program::pubkey::MAX_SEED_LEN);
+        for position in 0..=u8::MAX {
+            let (stake, _) = Pubkey::create_with_seed_for_pubkey_index(
+                &stake_authority,
+                &user_wallet,
+                position,
+                &program_id,
+            )
+            .unwrap();
+            assert_ne!(stake, wallet);
+        }
+    }
+
+    #[test]
+    fn deserialize_versioned() {
+        let owner = Pubkey::new_unique();
+        let mut pool = ViewerStakePoolV1 {
+            version: StateVersion::V1,
+            owner,
+            ..Default::default()
+        };
+        pool.ranks[1] = RankV1 {
+            minimal_staking_time: 60,
+            amount: 100,
+        };
+        let pool = ViewerStakePool::deserialize_versioned(&pool.try_to_vec().unwrap()).unwrap();
+        assert_eq!(pool.version, StateVersion::V4);
+        assert_eq!(pool.owner, owner);
+        assert_eq!(pool.ranks[1].minimal_staking_time, 60);
+        assert_eq!(pool.ranks[1].amount, 100);
+        assert_eq!(pool.ranks[1].reward_multiplier, 0);
+        assert_eq!(pool.reward_mint, Pubkey::default());
+        let data = pool.try_to_vec().unwrap();
+        assert_eq!(
+            ViewerStakePool::deserialize_versioned(&data).unwrap().owner,
+            owner
+        );
+
+        let pool = ViewerStakePoolV2 {
+            version: StateVersion::V2,
+            owner,
+            total_staked: 1000,
+            staker_count: 2,
+            ..Default::default()
+        };
+        let pool = ViewerStakePool::deserialize_versioned(&pool.try_to_vec().unwrap()).unwrap();
+        assert_eq!(pool.version, StateVersion::V4);
+        assert_eq!(pool.owner, owner);
+        assert_eq!(pool.total_staked, 1000);
+        assert_eq!(pool.staker_count, 2);
+        assert_eq!(pool.reward_mint, Pubkey::default());
+
+        let reward_mint = Pubkey::new_unique();
+        let pool = ViewerStakePoolV3 {
+            version: StateVersion::V3,
+            owner,
+            total_staked: 1000,
+            reward_mint,
+            ..Default::default()
+        };
+        let pool = ViewerStakePool::deserialize_versioned(&pool.try_to_vec().unwrap()).unwrap();
+        assert_eq!(pool.version, StateVersion::V4);
+        assert_eq!(pool.total_staked, 1000);
+        assert_eq!(pool.reward_mint, reward_mint);
+        assert_eq!(pool.capacity, 0);
+
+        let stake = ViewerStakeV1 {
+            version: StateVersion::V1,
+            staked_at: 10,
+            staked_until: 20,
+            owner,
+            amount: 1000,
+        };
+        let stake = ViewerStake::deserialize_versioned(&stake.try_to_vec().unwrap()).unwrap();
+        assert_eq!(stake.version, StateVersion::V2);
+        assert_eq!(stake.duration(), 10);
+        assert_eq!(stake.owner, owner);
+        assert_eq!(stake.amount, 1000);
+        assert_eq!(stake.position, 0);
+        assert_eq!(stake.reward(), Some(0));
+
+        assert!(ViewerStake::deserialize_versioned(&[0; ViewerStake::LEN]).is_err());
+    }
+
+    #[test]
+    fn reward() {
+        let hour = 60 * 60;
+        let mut pool = ViewerStakePool::default();
+        pool.ranks[1] = Rank {
+            minimal_staking_time: hour,
+            amount: 100,
+            reward_multiplier: Rank::ONE as u64,
+        };
+        pool.ranks[2] = Rank {
+            minimal_staking_time: 2 * hour,
+            amount: 1000,
+            reward_multiplier: 2 * Rank::ONE as u64,
+        };
+        pool.ranks[3] = Rank {
+            minimal_staking_time: 3 * hour,
+            amount: 10000,
+            reward_multiplier: 3 * Rank::ONE as u64,
+        };
+
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: Rank::YEAR,
+            amount: 1000,
+            ..Default::default()
+        };
+        assert_eq!(pool.rank_for(stake.amount, stake.duration()), Some(2));
+        stake.reward_multiplier = pool.reward_multiplier(stake.amount, stake.duration());
+        assert_eq!(stake.reward(), Some(2000));
+
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: Rank::YEAR / 2,
+            amount: 500,
+            ..Default::default()
+        };
+        assert_eq!(pool.rank_for(stake.amount, stake.duration()), Some(1));
+        stake.reward_multiplier = pool.reward_multiplier(stake.amount, stake.duration());
+        assert_eq!(stake.reward(), Some(250));
+        assert_eq!(stake.reward_for(100), Some(50));
+
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: hour,
+            amount: 10,
+            ..Default::default()
+        };
+        assert_eq!(pool.rank_for(stake.amount, stake.duration()), Some(0));
+        stake.reward_multiplier = pool.reward_multiplier(stake.amount, stake.duration());
+        assert_eq!(stake.reward(), Some(0));
+
+        pool.ranks[2].reward_multiplier = 0;
+        let stake = ViewerStake {
+            staked_at: 0,
+            staked_until: Rank::YEAR,
+            amount: 1000,
+            reward_multiplier: 2 * Rank::ONE as u64,
+            ..Default::default()
+        };
+        assert_eq!(pool.reward_multiplier(stake.amount, stake.duration()), 0);
+        assert_eq!(stake.reward(), Some(2000), "terms staked under are kept");
+    }
+
+    #[test]
+    fn reward_at() {
+        let hour = 60 * 60;
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: 2 * hour,
+            amount: 1000,
+            reward_multiplier: 365 * 24 * Rank::ONE as u64,
+            ..Default::default()
+        };
+        assert_eq!(stake.reward(), Some(2000));
+        assert_eq!(stake.reward_at(hour), Some(1000));
+        assert_eq!(stake.reward_at(3 * hour), Some(2000));
+
+        stake.carried_reward = stake.reward_at(hour).unwrap();
+        stake.staked_at = hour;
+        stake.staked_until = 3 * hour;
+        assert_eq!(stake.reward(), Some(3000));
+        assert_eq!(stake.reward_for(500), Some(1500));
+        stake.withdraw(500).unwrap();
+        assert_eq!(stake.carried_reward, 500);
+        assert_eq!(stake.reward(), Some(1500));
+    }
+
+    #[test]
+    fn rank_of_positions() {
+        let hour = 60 * 60;
+        let mut pool = ViewerStakePool::default();
+        pool.ranks[0].amount = 100;
+        pool.ranks[1] = Rank {
+            minimal_staking_time: hour,
+            amount: 1000,
+            reward_multiplier: 0,
+        };
+        pool.ranks[2] = Rank {
+            minimal_staking_time: 2 * hour,
+            amount: 1000,
+            reward_multiplier: 0,
+        };
+        pool.ranks[3] = Rank {
+            minimal_staking_time: 2 * hour,
+            amount: 2000,
+            reward_multiplier: 0,
+        };
+
+        let stakes = [
+            ViewerStake {
+                staked_at: 0,
+                staked_until: hour,
+                amount: 600,
+                ..Default::default()
+            },
+            ViewerStake {
+                staked_at: 0,
+                staked_until: 2 * hour,
+                amount: 600,
+                position: 1,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(pool.rank_of(&stakes[0], 0), Some(0));
+        assert_eq!(pool.rank_of(&stakes[1], 0), Some(0));
+        assert_eq!(pool.rank_of_positions(&stakes, 0), Some(1));
+        assert_eq!(pool.rank_of_positions(&stakes, hour), Some(0));
+        assert_eq!(pool.rank_of_positions(&stakes[..0], 0), None);
+    }
+
+    #[test]
+    fn early_unstake_penalty() {
+        let mut stake = ViewerStake::default();
+        assert_eq!(stake.early_unstake_penalty(1000), Some(0));
+        stake.early_unstake_penalty_bps = 250;
+        assert_eq!(stake.early_unstake_penalty(1000), Some(25));
+        assert_eq!(stake.early_unstake_penalty(39), Some(0));
+        stake.early_unstake_penalty_bps = ViewerStakePool::BPS;
+        assert_eq!(stake.early_unstake_penalty(u64::MAX), Some(u64::MAX));
+    }
+
+    #[test]
+    fn account_stake() {
+        let mut pool = ViewerStakePool::default();
+        pool.ranks[1] = Rank {
+            minimal_staking_time: Rank::YEAR,
+            amount: 1000,
+            reward_multiplier: Rank::ONE as u64,
+        };
+        pool.ranks[2] = Rank {
+            amount: u64::MAX,
+            ..pool.ranks[1]
+        };
+        pool.ranks[3] = pool.ranks[2];
+        let mut empty = ViewerStake::default();
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: Rank::YEAR,
+            amount: 1000,
+            reward_multiplier: Rank::ONE as u64,
+            ..Default::default()
+        };
+        pool.account_stake(&ViewerStake::default(), &mut stake).unwrap();
+        assert_eq!(pool.total_staked, 1000);
+        assert_eq!(pool.allocated_rewards, 1000);
+        assert_eq!(pool.unallocated_rewards(1500), 500);
+        assert_eq!(stake.rank, 1);
+        assert_eq!(pool.staker_count, 1);
+        assert_eq!(pool.rank_staker_counts, [0, 1, 0, 0]);
+
+        let before = stake.clone();
+        stake.withdraw(400).unwrap();
+        pool.account_stake(&before, &mut stake).unwrap();
+        assert_eq!(pool.total_staked, 600);
+        assert_eq!(pool.allocated_rewards, 600);
+        assert_eq!(stake.rank, 0);
+        assert_eq!(pool.staker_count, 1);
+        assert_eq!(pool.rank_staker_counts, [1, 0, 0, 0]);
+
+        pool.account_stake(&stake, &mut empty).unwrap();
+        assert_eq!(pool.total_staked, 0);
+        assert_eq!(pool.allocated_rewards, 0);
+        assert_eq!(pool.staker_count, 0);
+        assert_eq!(pool.rank_staker_counts, [0; 4]);
+        // stakes from before totals were tracked
+        pool.account_stake(&before, &mut empty).unwrap();
+        assert_eq!(pool.total_staked, 0);
+        assert_eq!(pool.staker_count, 0);
+        assert_eq!(pool.unallocated_rewards(1500), 1500);
+    }
+
+    #[test]
+    fn top_up() {
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: Rank::YEAR,
+            amount: 1000,
+            reward_multiplier: Rank::ONE as u64,
+            ..Default::default()
+        };
+        stake.top_up(1000, Rank::YEAR / 2, Rank::ONE as u64).unwrap();
+        assert_eq!(stake.amount, 2000);
+        assert_eq!(stake.staked_until, Rank::YEAR);
+        assert_eq!(stake.reward_multiplier, Rank::ONE as u64);
+        assert_eq!(stake.reward(), Some(1000 + 500));
+        assert_eq!(stake.reward_for(1000), Some(750));
+
+        stake.withdraw(1000).unwrap();
+        assert_eq!(stake.reward(), Some(750));
+        assert!(stake.withdraw(1001).is_none());
+
+        stake.top_up(1000, 2 * Rank::YEAR, Rank::ONE as u64).unwrap();
+        assert_eq!(stake.reward(), Some(750), "top up after lock lapsed earns nothing");
+    }
+
+    #[test]
+    fn top_up_multiplier_applies_to_added_amount() {
+        let mut stake = ViewerStake {
+            staked_at: 0,
+            staked_until: Rank::YEAR,
+            amount: 1000,
+            reward_multiplier: Rank::ONE as u64,
+            ..Default::default()
+        };
+        stake
+            .top_up(1000, Rank::YEAR / 2, 4 * Rank::ONE as u64)
+            .unwrap();
+        assert_eq!(stake.reward_multiplier, 2 * Rank::ONE as u64);
+        assert_eq!(
+            stake.reward(),
+            Some(1000 + 2000),
+            "existing amount keeps its multiplier"
+        );
+    }
+
+    #[test]
+    fn ranks_ordered() {
+        let hour = 60 * 60;
+        let mut ranks = [Rank::default(); 4];
+        assert!(ViewerStakePool::ranks_ordered(&ranks));
+        ranks[1].minimal_staking_time = hour;
+        ranks[1].amount = 100;
+        ranks[2] = ranks[1];
+        ranks[3] = Rank {
+            minimal_staking_time: 2 * hour,
+            amount: 1000,
+            reward_multiplier: 0,
+        };
+        assert!(ViewerStakePool::ranks_ordered(&ranks));
+        ranks[2].amount = 10000;
+        assert!(!ViewerStakePool::ranks_ordered(&ranks));
+        ranks[2].amount = 100;
+        ranks[2].minimal_staking_time = 3 * hour;
+        assert!(!ViewerStakePool::ranks_ordered(&ranks));
+    }
+
+    #[test]
+    fn rank_of() {
+        let hour = 60 * 60;
+        let mut pool = ViewerStakePool::default();
+        pool.ranks[0].amount = 100;
+        pool.ranks[1] = Rank {
+            minimal_staking_time: hour,
+            amount: 100,
+            reward_multiplier: 0,
+        };
+        pool.ranks[2] = Rank {
+            minimal_staking_time: 2 * hour,
+            amount: 1000,
+            reward_multiplier: 0,
+        };
+        pool.ranks[3] = Rank {
+            minimal_staking_time: 3 * hour,
+            amount: 1000,
+            reward_multiplier: 0,
+        };
+
+        let stake = ViewerStake {
+            staked_at: 0,
+            staked_until: 2 * hour,
+            amount: 1000,
+            ..Default::default()
+        };
+        assert_eq!(pool.rank_of(&stake, 0), Some(2));
+        assert_eq!(pool.rank_of(&stake, 2 * hour - 1), Some(2));
+        assert_eq!(pool.rank_of(&stake, 2 * hour), Some(0));
+
+        let stake = ViewerStake {
+            amount: 500,
+            ..stake
+        };
+        assert_eq!(pool.rank_of(&stake, 0), Some(1));
+
+        let stake = ViewerStake { amount: 10, ..stake };
+        assert_eq!(pool.rank_of(&stake, 0), None);
+    }
+
+    #[test]
+    fn check_stake_limits() {
+        let mut pool = ViewerStakePool::default();
+        let stake = ViewerStake {
+            amount: 1000,
+            ..Default::default()
+        };
+        let other_position = ViewerStake {
+            amount: 500,
+            ..Default::default()
+        };
+        let mut wallet = ViewerWallet::default();
+        wallet.account_stake(&ViewerStake::default(), &stake).unwrap();
+        wallet
+            .account_stake(&ViewerStake::default(), &other_position)
+            .unwrap();
+        assert_eq!(wallet.total_staked, 1500);
+        pool.total_staked = 5000;
+        assert!(pool.check_stake_limits(&stake, &wallet).is_ok());
+
+        pool.min_stake = 1001;
+        assert!(pool.check_stake_limits(&stake, &wallet).is_err());
+        pool.min_stake = 1000;
+        assert!(pool.check_stake_limits(&stake, &wallet).is_ok());
+
+        pool.max_stake_per_wallet = 1000;
+        assert!(
+            pool.check_stake_limits(&stake, &wallet).is_err(),
+            "cap is checked across positions"
+        );
+        pool.max_stake_per_wallet = 1500;
+        assert!(pool.check_stake_limits(&stake, &wallet).is_ok());
+
+        pool.capacity = 4999;
+        assert!(pool.check_stake_limits(&stake, &wallet).is_err());
+        pool.capacity = 5000;
+        assert!(pool.check_stake_limits(&stake, &wallet).is_ok());
+
+        wallet.account_stake(&stake, &ViewerStake::default()).unwrap();
+        assert_eq!(wallet.total_staked, 500);
+        wallet.account_stake(&stake, &ViewerStake::default()).unwrap();
+        assert_eq!(wallet.total_staked, 0, "uncounted positions do not underflow");
     }
 }
@@ -1,23 +1,34 @@
 use crate::{
-    instruction::StakeInput,
-    state::{ViewerStake, ViewerStakePool},
+    errors,
+    instruction::{
+        AddToStakeInput, ExtendLockInput, ProposeOwnerInput, RewardsInput,
+        SetEarlyUnstakePenaltyInput, SetPoolModeInput, SetStakeLimitsInput, SetViewerKycInput,
+        StakeInput, StakeIntentInput, UnstakeInput, UnstakeIntentInput, UpdateRanksInput,
+    },
+    state::{ViewerStake, ViewerStakePool, ViewerStakePoolV1, ViewerStakeV1, ViewerWallet},
     tests_helpers::*,
-    transactions::{self, warp_seconds},
+    transactions::{self, get_clock, warp, warp_seconds},
 };
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::convert::TryInto;
 use sator_sdk::program::PubkeyPatterns;
+use sator_sdk::state::StateVersion;
+use sator_sdk::types::TokenAmount;
 use solana_program::native_token::sol_to_lamports;
+use solana_program::rent::Rent;
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
+    transaction::Transaction,
 };
 
-use sator_sdk_test::spl_transactions;
+use sator_sdk_test::{banks_client::assert_program_error, spl_transactions};
 
 use crate::{
     instruction::InitializeStakePoolInput, processor::process_instruction, stake_viewer_program_id,
-    transactions::initialize_stake_pool, types::Rank,
+    transactions::initialize_stake_pool, types::{Rank, RankV1},
 };
 
 pub fn new_program_test() -> ProgramTest {
@@ -30,6 +41,185 @@ pub fn new_program_test() -> ProgramTest {
     program_test
 }
 
+/// simulates transaction with [crate::instruction::Instruction::GetRank] and reads rank from return data
+pub async fn simulate_rank(client: &mut BanksClient, transaction: Transaction) -> Option<u8> {
+    let simulation = client.simulate_transaction(transaction).await.unwrap();
+    let mut data = simulation
+        .simulation_details
+        .unwrap()
+        .return_data
+        .map_or(vec![], |x| x.data);
+    // runtime trims trailing zeros of return data, so `None` comes empty
+    if data.is_empty() {
+        return None;
+    }
+    data.resize(2, 0);
+    Option::<u8>::try_from_slice(&data).unwrap()
+}
+
+/// accounts of stake pool created by [setup_stake_pool]
+pub struct StakePoolTest {
+    pub client: ProgramTestContext,
+    pub fee_payer: Keypair,
+    pub stake_pool_owner: Keypair,
+    pub user_wallet: Keypair,
+    pub mint: Keypair,
+    pub stake_pool: Pubkey,
+    pub stake_pool_token_account: Pubkey,
+    pub user_token_account: Pubkey,
+}
+
+/// creates mint, stake pool from `input` and `user_wallet` token account with `user_amount` tokens
+pub async fn setup_stake_pool(
+    input: InitializeStakePoolInput,
+    user_amount: TokenAmount,
+) -> StakePoolTest {
+    let mut program_test = new_program_test();
+
+    let stake_pool_owner = Keypair::new();
+    let fee_payer = Keypair::new();
+    let user_wallet = Keypair::new();
+
+    program_test.add_account(
+        fee_payer.pubkey(),
+        Account {
+            lamports: u64::MAX / 32,
+            ..<_>::default()
+        },
+    );
+
+    let mint = Keypair::new();
+    let mut client = program_test.start_with_context().await;
+
+    let transaction = spl_transactions::create_initialize_mint(
+        &fee_payer,
+        &mint,
+        &stake_pool_owner.pubkey(),
+        sol_to_lamports(10.),
+        2,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, stake_pool) = initialize_stake_pool(
+        &fee_payer,
+        &stake_pool_owner,
+        &mint.pubkey(),
+        input,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, user_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &mint.pubkey(),
+        &user_wallet,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = spl_transactions::mint_to(
+        &fee_payer,
+        &mint.pubkey(),
+        &user_token_account.pubkey(),
+        &stake_pool_owner,
+        user_amount,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_authority = Pubkey::find_program_address_for_pubkey(
+        &stake_pool.pubkey(),
+        &crate::stake_viewer_program_id(),
+    );
+    let stake_pool_token_account = Pubkey::create_with_seed(
+        &stake_authority.0,
+        "ViewerStakePool::token_account",
+        &spl_token::id(),
+    )
+    .unwrap();
+
+    StakePoolTest {
+        client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool: stake_pool.pubkey(),
+        stake_pool_token_account,
+        user_token_account: user_token_account.pubkey(),
+    }
+}
+
+/// mints `amount` to new token account of `stake_pool_owner` and funds reward reserve of `stake_pool` from it
+pub async fn fund_rewards(
+    client: &mut ProgramTestContext,
+    fee_payer: &Keypair,
+    mint: &Keypair,
+    stake_pool_owner: &Keypair,
+    stake_pool: &Pubkey,
+    amount: TokenAmount,
+) {
+    let (transaction, owner_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &mint.pubkey(),
+        stake_pool_owner,
+        fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = spl_transactions::mint_to(
+        fee_payer,
+        &mint.pubkey(),
+        &owner_token_account.pubkey(),
+        stake_pool_owner,
+        amount,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::fund_rewards(
+        fee_payer,
+        stake_pool,
+        &mint.pubkey(),
+        &owner_token_account.pubkey(),
+        stake_pool_owner,
+        RewardsInput { amount },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
 /// see dbg! writes for full flow tracing
 #[tokio::test]
 async fn flow() {
@@ -88,20 +278,26 @@ async fn flow() {
                 Rank {
                     minimal_staking_time: 0,
                     amount,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 1 * hour,
                     amount: amount * 2,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 2 * hour,
                     amount: amount * 3,
+                    reward_multiplier: 0,
                 },
                 Rank {
                     minimal_staking_time: 3 * hour,
                     amount: amount * 4,
+                    reward_multiplier: 0,
                 },
             ],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
         },
         client.last_blockhash,
     );
@@ -136,20 +332,15 @@ async fn flow() {
     assert!(stake_state.ranks[3].amount > 0);
 
     dbg!("Minting to stake pool wallet");
-    let transaction = spl_transactions::mint_to(
+    fund_rewards(
+        &mut client,
         &fee_payer,
-        &mint.pubkey(),
-        &stake_pool_token_account,
+        &mint,
         &stake_pool_owner,
+        &stake_pool.pubkey(),
         10000000000,
-        client.last_blockhash,
-    );
-
-    client
-        .banks_client
-        .process_transaction(transaction)
-        .await
-        .unwrap();
+    )
+    .await;
 
     dbg!("Create user token account wallet");
     let (transaction, user_token_account) = spl_transactions::create_token_account(
@@ -191,6 +382,7 @@ async fn flow() {
         StakeInput {
             amount: 1000,
             duration: stake_duration,
+            position: 0,
         },
         client.last_blockhash,
     );
@@ -208,7 +400,7 @@ async fn flow() {
     assert_eq!(user_token_account_state.amount, 999000);
     let stake_pool_token_account_state =
         get_token_account_state(&mut client.banks_client, &stake_pool_token_account).await;
-    assert_eq!(stake_pool_token_account_state.amount, 10000001000);
+    assert_eq!(stake_pool_token_account_state.amount, 1000);
 
     let viewer_stake_account_state: ViewerStake = client
         .banks_client
@@ -227,6 +419,10 @@ async fn flow() {
         &stake_pool.pubkey(),
         &user_token_account.pubkey(),
         &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
         client.last_blockhash,
     );
 
@@ -245,6 +441,7 @@ async fn flow() {
         StakeInput {
             amount: 2000,
             duration: stake_duration,
+            position: 0,
         },
         client.last_blockhash,
     );
@@ -278,6 +475,10 @@ async fn flow() {
         &stake_pool.pubkey(),
         &user_token_account.pubkey(),
         &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
         client.last_blockhash,
     );
 
@@ -298,3 +499,3125 @@ async fn flow() {
         .await
         .expect_err("account was burned");
 }
+
+#[tokio::test]
+async fn unstake_fails_if_pool_underfunded() {
+    let hour = 60 * 60;
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        stake_pool_token_account,
+        user_token_account,
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank {
+                minimal_staking_time: hour,
+                amount: 1000,
+                reward_multiplier: 365 * 24 * Rank::ONE as u64,
+            }; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        2000,
+    )
+    .await;
+
+    for position in 0..2 {
+        let (transaction, _) = transactions::stake(
+            &fee_payer,
+            &user_wallet,
+            &stake_pool,
+            &user_token_account,
+            StakeInput {
+                amount: 1000,
+                duration: hour,
+                position,
+            },
+            client.last_blockhash,
+        );
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    warp_seconds(&mut client, 2 * hour).await;
+
+    // principal of other position is not used for reward
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::UnstakeRewardPoolUnderfunded,
+    );
+    let stake_pool_token_account_state =
+        get_token_account_state(&mut client.banks_client, &stake_pool_token_account).await;
+    assert_eq!(stake_pool_token_account_state.amount, 2000);
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        1000,
+    )
+    .await;
+
+    // new blockhash so unstake is not rejected as already processed
+    warp(&mut client, 100).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 2000);
+    let stake_pool_token_account_state =
+        get_token_account_state(&mut client.banks_client, &stake_pool_token_account).await;
+    assert_eq!(stake_pool_token_account_state.amount, 1000);
+}
+
+#[tokio::test]
+async fn update_ranks_keeps_terms_of_existing_stakes() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 1000,
+        reward_multiplier: 365 * 24 * Rank::ONE as u64,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        1000,
+    )
+    .await;
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let ranks = [Rank {
+        reward_multiplier: 0,
+        ..rank
+    }; 4];
+
+    let transaction = transactions::update_ranks(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        UpdateRanksInput { ranks },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let mut unordered = ranks;
+    unordered[0].amount = 2000;
+    let transaction = transactions::update_ranks(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        UpdateRanksInput { ranks: unordered },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::RanksMustBeOrderedByTimeAndAmount,
+    );
+
+    let transaction = transactions::update_ranks(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        UpdateRanksInput { ranks },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.ranks[0].reward_multiplier, 0);
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.reward_multiplier, rank.reward_multiplier);
+
+    warp_seconds(&mut client, 2 * hour).await;
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 2000);
+}
+
+#[tokio::test]
+async fn transfer_ownership() {
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank::default(); 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        0,
+    )
+    .await;
+    let new_owner = Keypair::new();
+
+    let transaction =
+        transactions::accept_owner(&fee_payer, &stake_pool, &new_owner, client.last_blockhash);
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::OnlyPendingOwnerCanAcceptOwnership,
+    );
+
+    let transaction = transactions::propose_owner(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        ProposeOwnerInput {
+            pending_owner: user_wallet.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let transaction = transactions::propose_owner(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        ProposeOwnerInput {
+            pending_owner: new_owner.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.owner, stake_pool_owner.pubkey());
+    assert_eq!(stake_pool_state.pending_owner, new_owner.pubkey());
+
+    let transaction =
+        transactions::accept_owner(&fee_payer, &stake_pool, &user_wallet, client.last_blockhash);
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::OnlyPendingOwnerCanAcceptOwnership,
+    );
+
+    // new blockhash so accept is not rejected as already processed
+    warp(&mut client, 100).await;
+    let transaction =
+        transactions::accept_owner(&fee_payer, &stake_pool, &new_owner, client.last_blockhash);
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.owner, new_owner.pubkey());
+    assert_eq!(stake_pool_state.pending_owner, Pubkey::default());
+
+    let transaction = transactions::update_ranks(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        UpdateRanksInput {
+            ranks: [Rank::default(); 4],
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+    let transaction = transactions::update_ranks(
+        &fee_payer,
+        &stake_pool,
+        &new_owner,
+        UpdateRanksInput {
+            ranks: [Rank::default(); 4],
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn stake_requires_kyc() {
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank::default(); 4],
+            kyc_required: true,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 0,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakeViewerMustBeKycVerified,
+    );
+
+    let transaction = transactions::set_viewer_kyc(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet.pubkey(),
+        &user_wallet,
+        SetViewerKycInput { kyc_verified: true },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let transaction = transactions::set_viewer_kyc(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet.pubkey(),
+        &stake_pool_owner,
+        SetViewerKycInput { kyc_verified: true },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(&stake_pool, &stake_viewer_program_id());
+    let (viewer_wallet, _) = ViewerWallet::derive(
+        &stake_authority,
+        &user_wallet.pubkey(),
+        &stake_viewer_program_id(),
+    )
+    .unwrap();
+    let viewer_wallet_state: ViewerWallet = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_wallet)
+        .await
+        .unwrap();
+    assert!(viewer_wallet_state.kyc_verified);
+    assert_eq!(viewer_wallet_state.owner, user_wallet.pubkey());
+    assert!(client
+        .banks_client
+        .get_account(viewer_stake_account)
+        .await
+        .unwrap()
+        .is_none());
+
+    // new blockhash so stake is not rejected as already processed
+    warp(&mut client, 100).await;
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 0,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.amount, 1000);
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    assert!(client
+        .banks_client
+        .get_account(viewer_stake_account)
+        .await
+        .unwrap()
+        .is_none());
+
+    warp(&mut client, 100).await;
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 0,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn partial_unstake() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 1000,
+        reward_multiplier: 365 * 24 * Rank::ONE as u64,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [
+                Rank {
+                    minimal_staking_time: 0,
+                    reward_multiplier: 0,
+                    ..rank
+                },
+                rank,
+                rank,
+                rank,
+            ],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        2000,
+    )
+    .await;
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        2000,
+    )
+    .await;
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 2000,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    warp_seconds(&mut client, 2 * hour).await;
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: Some(3000),
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::UnstakeAmountExceedsStake,
+    );
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: Some(500),
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_eq!(
+        simulate_rank(&mut client.banks_client, transaction.clone()).await,
+        Some(0)
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 500 + 500);
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.amount, 1500);
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: Some(1000),
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_eq!(
+        simulate_rank(&mut client.banks_client, transaction.clone()).await,
+        None
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 1000 + 2000);
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 3000 + 1000);
+    client
+        .banks_client
+        .get_account_data_with_borsh::<ViewerStake>(viewer_stake_account)
+        .await
+        .expect_err("account was burned");
+}
+
+#[tokio::test]
+async fn early_unstake_withholds_penalty() {
+    let hour = 60 * 60;
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank {
+                minimal_staking_time: hour,
+                amount: 0,
+                reward_multiplier: 365 * 24 * Rank::ONE as u64,
+            }; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        2000,
+    )
+    .await;
+
+    let (transaction, treasury) = spl_transactions::create_token_account(
+        10000000,
+        &mint.pubkey(),
+        &stake_pool_owner,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let treasury = treasury.pubkey();
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 10 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::early_unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        &treasury,
+        UnstakeInput {
+            amount: Some(1000),
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::EarlyUnstakeIsNotEnabled,
+    );
+
+    let transaction = transactions::set_early_unstake_penalty(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &treasury,
+        SetEarlyUnstakePenaltyInput {
+            penalty_bps: ViewerStakePool::BPS + 1,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::EarlyUnstakePenaltyMustNotExceedWholeAmount,
+    );
+
+    let transaction = transactions::set_early_unstake_penalty(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        &treasury,
+        SetEarlyUnstakePenaltyInput { penalty_bps: 0 },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let transaction = transactions::set_early_unstake_penalty(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &treasury,
+        SetEarlyUnstakePenaltyInput { penalty_bps: 1000 },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 10 * hour,
+            position: 1,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.early_unstake_penalty_bps, 1000);
+
+    // raised penalty applies only to later stakes
+    let transaction = transactions::set_early_unstake_penalty(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &treasury,
+        SetEarlyUnstakePenaltyInput {
+            penalty_bps: ViewerStakePool::BPS,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::early_unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        &treasury,
+        UnstakeInput {
+            amount: Some(400),
+            position: 1,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 360);
+    let treasury_state = get_token_account_state(&mut client.banks_client, &treasury).await;
+    assert_eq!(treasury_state.amount, 40);
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.amount, 600);
+
+    let transaction = transactions::early_unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        &treasury,
+        UnstakeInput {
+            amount: None,
+            position: 1,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 360 + 540);
+    let treasury_state = get_token_account_state(&mut client.banks_client, &treasury).await;
+    assert_eq!(treasury_state.amount, 40 + 60);
+    client
+        .banks_client
+        .get_account_data_with_borsh::<ViewerStake>(viewer_stake_account)
+        .await
+        .expect_err("account was burned");
+
+    // staked before penalty was set
+    let transaction = transactions::early_unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        &treasury,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 360 + 540 + 1000);
+    let treasury_state = get_token_account_state(&mut client.banks_client, &treasury).await;
+    assert_eq!(treasury_state.amount, 40 + 60);
+}
+
+#[tokio::test]
+async fn independent_positions() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 1000,
+        reward_multiplier: 0,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [
+                Rank {
+                    minimal_staking_time: 0,
+                    amount: 100,
+                    reward_multiplier: 0,
+                },
+                rank,
+                Rank {
+                    minimal_staking_time: 2 * hour,
+                    ..rank
+                },
+                Rank {
+                    minimal_staking_time: 2 * hour,
+                    amount: 2000,
+                    ..rank
+                },
+            ],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        2000,
+    )
+    .await;
+
+    let (transaction, first_position) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 600,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let (transaction, second_position) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 600,
+            duration: 2 * hour,
+            position: 1,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    assert_ne!(first_position, second_position);
+
+    let second_position_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(second_position)
+        .await
+        .unwrap();
+    assert_eq!(second_position_state.position, 1);
+    assert_eq!(second_position_state.amount, 600);
+
+    for (positions, expected) in [(&[0][..], Some(0)), (&[1][..], Some(0)), (&[0, 1][..], Some(1))] {
+        let transaction = transactions::get_rank(
+            &fee_payer,
+            &stake_pool,
+            &user_wallet.pubkey(),
+            positions,
+            client.last_blockhash,
+        );
+        assert_eq!(
+            simulate_rank(&mut client.banks_client, transaction).await,
+            expected
+        );
+    }
+    let transaction = transactions::get_rank(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet.pubkey(),
+        &[1, 1],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::GetRankPositionsMustBeUnique,
+    );
+
+    warp_seconds(&mut client, hour).await;
+
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 1,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::UnstakeCanBeDoneOnlyAfterStakeTimeLapsed,
+    );
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 800 + 600);
+    client
+        .banks_client
+        .get_account_data_with_borsh::<ViewerStake>(first_position)
+        .await
+        .expect_err("account was burned");
+    let second_position_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(second_position)
+        .await
+        .unwrap();
+    assert_eq!(second_position_state.amount, 600);
+}
+
+#[tokio::test]
+async fn add_to_stake_keeps_lock() {
+    let hour = 60 * 60;
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank {
+                minimal_staking_time: hour,
+                amount: 0,
+                // hour of stake pays whole staked amount
+                reward_multiplier: 365 * 24 * Rank::ONE as u64,
+            }; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        6000,
+    )
+    .await;
+
+    let (transaction, third_party_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &mint.pubkey(),
+        &stake_pool_owner,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let third_party_token_account = third_party_token_account.pubkey();
+    let transaction = spl_transactions::mint_to(
+        &fee_payer,
+        &mint.pubkey(),
+        &third_party_token_account,
+        &stake_pool_owner,
+        1000,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 3 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let staked: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+
+    warp_seconds(&mut client, hour).await;
+    let transaction = transactions::add_to_stake(
+        &fee_payer,
+        &stake_pool,
+        &third_party_token_account,
+        &user_wallet.pubkey(),
+        &stake_pool_owner,
+        AddToStakeInput {
+            amount: 1000,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let topped_up_at = get_clock(&mut client).await.unix_timestamp;
+
+    let topped_up: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(topped_up.amount, 2000);
+    assert_eq!(topped_up.staked_at, staked.staked_at);
+    assert_eq!(topped_up.staked_until, staked.staked_until);
+    assert_eq!(
+        topped_up.late_stake_seconds,
+        1000 * (topped_up_at - staked.staked_at) as u128
+    );
+
+    warp_seconds(&mut client, 3 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    let late_reward = user_token_account_state.amount - (2000 + 3000);
+    assert!(late_reward < 2000, "{}", late_reward);
+}
+
+#[tokio::test]
+async fn restake_keeps_earned_reward() {
+    let hour = 60 * 60;
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank {
+                minimal_staking_time: hour,
+                amount: 0,
+                // hour of stake pays whole staked amount
+                reward_multiplier: 365 * 24 * Rank::ONE as u64,
+            }; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        2000,
+    )
+    .await;
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        10000,
+    )
+    .await;
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 2 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let staked: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+
+    warp_seconds(&mut client, hour).await;
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 2 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let restaked: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    let earned = 1000 * (restaked.staked_at - staked.staked_at) as u64 / hour as u64;
+    assert!(earned > 1000 && earned < 2000, "{}", earned);
+    assert_eq!(restaked.carried_reward, earned);
+    assert_eq!(restaked.late_stake_seconds, 0);
+
+    warp_seconds(&mut client, 3 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // two hours of whole amount after restake and hour of first amount before
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 2000 + 4000 + earned);
+}
+
+#[tokio::test]
+async fn add_to_stake_requires_active_lock() {
+    let hour = 60 * 60;
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank {
+                minimal_staking_time: hour,
+                amount: 0,
+                reward_multiplier: 0,
+            }; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        3000,
+    )
+    .await;
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 2 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    warp_seconds(&mut client, hour + 60).await;
+    let transaction = transactions::add_to_stake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        &user_wallet,
+        AddToStakeInput {
+            amount: 1000,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakeStakingTimeMustBeMoreThanMinimal,
+    );
+
+    warp_seconds(&mut client, hour).await;
+    let transaction = transactions::add_to_stake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        &user_wallet,
+        AddToStakeInput {
+            amount: 1000,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::TopUpRequiresActiveLock,
+    );
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 2000);
+    let _ = mint;
+}
+
+#[tokio::test]
+async fn add_to_stake_rank_applies_to_added_amount() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: 0,
+        amount: 0,
+        reward_multiplier: Rank::ONE as u64,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [
+                rank,
+                rank,
+                rank,
+                Rank {
+                    amount: 2000,
+                    reward_multiplier: 3 * Rank::ONE as u64,
+                    ..rank
+                },
+            ],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        2000,
+    )
+    .await;
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: 2 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let staked: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(staked.reward_multiplier, Rank::ONE as u64);
+
+    warp_seconds(&mut client, hour).await;
+    let transaction = transactions::add_to_stake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        &user_wallet,
+        AddToStakeInput {
+            amount: 1000,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_eq!(
+        simulate_rank(&mut client.banks_client, transaction.clone()).await,
+        Some(3)
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let topped_up_at = get_clock(&mut client).await.unix_timestamp;
+
+    let topped_up: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    let stake_seconds = 1000 * staked.duration() as u128;
+    let added_stake_seconds = 1000 * (staked.staked_until - topped_up_at) as u128;
+    assert_eq!(
+        topped_up.reward_multiplier as u128,
+        (stake_seconds + 3 * added_stake_seconds) * Rank::ONE
+            / (stake_seconds + added_stake_seconds),
+        "higher rank reached by top up pays only for added amount"
+    );
+    assert!(topped_up.reward_multiplier < 3 * Rank::ONE as u64);
+}
+
+#[tokio::test]
+async fn extend_lock_upgrades_rank() {
+    let hour = 60 * 60;
+    let rank = |hours: i64| Rank {
+        minimal_staking_time: hours * hour,
+        amount: 0,
+        reward_multiplier: hours as u64 * Rank::ONE as u64,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank(0), rank(1), rank(2), rank(3)],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let staked: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(staked.reward_multiplier, rank(1).reward_multiplier);
+
+    let transaction = transactions::extend_lock(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        ExtendLockInput {
+            duration: hour / 2,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakeStakingTimeMustBeMoreThanPrevious,
+    );
+
+    let transaction = transactions::extend_lock(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        ExtendLockInput {
+            duration: 3 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_eq!(
+        simulate_rank(&mut client.banks_client, transaction.clone()).await,
+        Some(3)
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let extended: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(extended.amount, 1000);
+    assert_eq!(extended.staked_at, staked.staked_at);
+    assert_eq!(extended.staked_until, staked.staked_at + 3 * hour);
+    assert_eq!(extended.reward_multiplier, rank(3).reward_multiplier);
+
+    warp_seconds(&mut client, 2 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::UnstakeCanBeDoneOnlyAfterStakeTimeLapsed,
+    );
+}
+
+#[tokio::test]
+async fn pause_and_emergency_unstake() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        reward_multiplier: 365 * 24 * Rank::ONE as u64,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        1000,
+    )
+    .await;
+
+    let transaction = transactions::set_pool_mode(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        SetPoolModeInput {
+            paused: true,
+            emergency: false,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let transaction = transactions::set_pool_mode(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        SetPoolModeInput {
+            paused: true,
+            emergency: false,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let stake_input = || StakeInput {
+        amount: 1000,
+        duration: hour,
+        position: 0,
+    };
+    let (transaction, viewer_stake_account) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        stake_input(),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolIsPaused,
+    );
+
+    let transaction = transactions::set_pool_mode(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        SetPoolModeInput {
+            paused: false,
+            emergency: false,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    warp(&mut client, 100).await;
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        stake_input(),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::set_pool_mode(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        SetPoolModeInput {
+            paused: false,
+            emergency: true,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 1000);
+    client
+        .banks_client
+        .get_account_data_with_borsh::<ViewerStake>(viewer_stake_account)
+        .await
+        .expect_err("account was burned");
+}
+
+#[tokio::test]
+async fn withdraw_unallocated_rewards_keeps_owed_rewards() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        // hour of stake pays whole staked amount
+        reward_multiplier: 365 * 24 * Rank::ONE as u64,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &mint,
+        &stake_pool_owner,
+        &stake_pool,
+        1500,
+    )
+    .await;
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.total_staked, 1000);
+    assert_eq!(stake_pool_state.allocated_rewards, 1000);
+    assert_eq!(stake_pool_state.staker_count, 1);
+    assert_eq!(stake_pool_state.rank_staker_counts, [0, 0, 0, 1]);
+
+    let (transaction, owner_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &mint.pubkey(),
+        &stake_pool_owner,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::withdraw_unallocated_rewards(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        &user_token_account,
+        RewardsInput { amount: 500 },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let transaction = transactions::withdraw_unallocated_rewards(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &owner_token_account.pubkey(),
+        RewardsInput { amount: 600 },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::WithdrawExceedsUnallocatedRewards,
+    );
+
+    let transaction = transactions::withdraw_unallocated_rewards(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &owner_token_account.pubkey(),
+        RewardsInput { amount: 500 },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let owner_token_account_state =
+        get_token_account_state(&mut client.banks_client, &owner_token_account.pubkey()).await;
+    assert_eq!(owner_token_account_state.amount, 500);
+
+    warp_seconds(&mut client, 2 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 2000);
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.total_staked, 0);
+    assert_eq!(stake_pool_state.allocated_rewards, 0);
+    assert_eq!(stake_pool_state.staker_count, 0);
+    assert_eq!(stake_pool_state.rank_staker_counts, [0; 4]);
+}
+
+#[tokio::test]
+async fn migrate_v1_accounts() {
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank::default(); 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        0,
+    )
+    .await;
+
+    // pool and stake as left by first version of program
+    let rank = RankV1 {
+        minimal_staking_time: 60,
+        amount: 1000,
+    };
+    let stake_pool_state = ViewerStakePoolV1 {
+        version: StateVersion::V1,
+        ranks: [rank; 4],
+        owner: stake_pool_owner.pubkey(),
+    };
+    client.set_account(
+        &stake_pool,
+        &Account {
+            lamports: Rent::default().minimum_balance(ViewerStakePoolV1::LEN),
+            data: stake_pool_state.try_to_vec().unwrap(),
+            owner: stake_viewer_program_id(),
+            ..<_>::default()
+        }
+        .into(),
+    );
+
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(&stake_pool, &stake_viewer_program_id());
+    let (viewer_stake_account, _) = Pubkey::create_with_seed_for_pubkey_index(
+        &stake_authority,
+        &user_wallet.pubkey(),
+        0,
+        &stake_viewer_program_id(),
+    )
+    .unwrap();
+    let viewer_stake_state = ViewerStakeV1 {
+        version: StateVersion::V1,
+        staked_at: 0,
+        staked_until: 120,
+        owner: user_wallet.pubkey(),
+        amount: 1000,
+    };
+    client.set_account(
+        &viewer_stake_account,
+        &Account {
+            lamports: Rent::default().minimum_balance(ViewerStakeV1::LEN),
+            data: viewer_stake_state.try_to_vec().unwrap(),
+            owner: stake_viewer_program_id(),
+            ..<_>::default()
+        }
+        .into(),
+    );
+
+    let transaction = transactions::migrate(
+        &fee_payer,
+        &stake_pool,
+        &[viewer_stake_account],
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.version, StateVersion::V4);
+    assert_eq!(stake_pool_state.owner, stake_pool_owner.pubkey());
+    assert_eq!(stake_pool_state.ranks[3].amount, 1000);
+    assert_eq!(stake_pool_state.total_staked, 1000);
+    assert_eq!(stake_pool_state.staker_count, 1);
+    assert_eq!(stake_pool_state.rank_staker_counts, [0, 0, 0, 1]);
+
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.version, StateVersion::V2);
+    assert_eq!(viewer_stake_state.owner, user_wallet.pubkey());
+    assert_eq!(viewer_stake_state.amount, 1000);
+    assert_eq!(viewer_stake_state.staked_until, 120);
+    assert_eq!(viewer_stake_state.rank, 3);
+
+    warp(&mut client, 100).await;
+    let transaction = transactions::migrate(
+        &fee_payer,
+        &stake_pool,
+        &[viewer_stake_account],
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.total_staked, 1000);
+    assert_eq!(stake_pool_state.staker_count, 1);
+
+    // migrated pool is handed over as any new one
+    let new_owner = Keypair::new();
+    let transaction = transactions::propose_owner(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        ProposeOwnerInput {
+            pending_owner: new_owner.pubkey(),
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let transaction = transactions::accept_owner(
+        &fee_payer,
+        &stake_pool,
+        &new_owner,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.owner, new_owner.pubkey());
+    assert_eq!(stake_pool_state.pending_owner, Pubkey::default());
+}
+
+#[tokio::test]
+async fn rewards_paid_in_reward_mint() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        // hour of stake pays whole staked amount
+        reward_multiplier: 365 * 24 * Rank::ONE as u64,
+    };
+    let reward_mint = Keypair::new();
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        mint,
+        stake_pool,
+        stake_pool_token_account,
+        user_token_account,
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: reward_mint.pubkey(),
+        },
+        1000,
+    )
+    .await;
+
+    let transaction = spl_transactions::create_initialize_mint(
+        &fee_payer,
+        &reward_mint,
+        &stake_pool_owner.pubkey(),
+        sol_to_lamports(10.),
+        2,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::fund_rewards(
+        &fee_payer,
+        &stake_pool,
+        &mint.pubkey(),
+        &user_token_account,
+        &user_wallet,
+        RewardsInput { amount: 1000 },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::RewardReserveMustHoldPoolMint,
+    );
+
+    fund_rewards(
+        &mut client,
+        &fee_payer,
+        &reward_mint,
+        &stake_pool_owner,
+        &stake_pool,
+        1000,
+    )
+    .await;
+
+    let (transaction, user_reward_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &reward_mint.pubkey(),
+        &user_wallet,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let stake_pool_token_account_state =
+        get_token_account_state(&mut client.banks_client, &stake_pool_token_account).await;
+    assert_eq!(stake_pool_token_account_state.amount, 1000);
+
+    warp_seconds(&mut client, 2 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        spl_token::error::TokenError::MintMismatch,
+    );
+
+    let transaction = transactions::unstake_with_reward_target(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_reward_token_account.pubkey(),
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 1000);
+    let user_reward_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_reward_token_account.pubkey())
+            .await;
+    assert_eq!(user_reward_token_account_state.amount, 1000);
+}
+
+#[tokio::test]
+async fn stake_limits() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        reward_multiplier: 0,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    let limits = || SetStakeLimitsInput {
+        min_stake: 100,
+        max_stake_per_wallet: 500,
+        capacity: 600,
+    };
+    let transaction = transactions::set_stake_limits(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        limits(),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let transaction = transactions::set_stake_limits(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        limits(),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let stake = |amount, position| StakeInput {
+        amount,
+        duration: hour,
+        position,
+    };
+    for (amount, position, error) in [
+        (50, 0, errors::Error::StakeAmountBelowMinimum),
+        (600, 0, errors::Error::StakeExceedsWalletCap),
+    ] {
+        let (transaction, _) = transactions::stake(
+            &fee_payer,
+            &user_wallet,
+            &stake_pool,
+            &user_token_account,
+            stake(amount, position),
+            client.last_blockhash,
+        );
+        assert_program_error(
+            client.banks_client.process_transaction(transaction).await,
+            error,
+        );
+    }
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        stake(400, 0),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        stake(200, 1),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakeExceedsWalletCap,
+    );
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        stake(100, 1),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(&stake_pool, &stake_viewer_program_id());
+    let (viewer_wallet, _) = ViewerWallet::derive(
+        &stake_authority,
+        &user_wallet.pubkey(),
+        &stake_viewer_program_id(),
+    )
+    .unwrap();
+    let viewer_wallet_state: ViewerWallet = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_wallet)
+        .await
+        .unwrap();
+    assert_eq!(viewer_wallet_state.total_staked, 500);
+
+    let transaction = transactions::set_stake_limits(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        SetStakeLimitsInput {
+            max_stake_per_wallet: 1000,
+            capacity: 550,
+            ..limits()
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        stake(100, 2),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakeExceedsPoolCapacity,
+    );
+
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.total_staked, 500);
+    assert_eq!(stake_pool_state.capacity, 550);
+
+    warp_seconds(&mut client, 2 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 1,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let viewer_wallet_state: ViewerWallet = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_wallet)
+        .await
+        .unwrap();
+    assert_eq!(viewer_wallet_state.total_staked, 400);
+}
+
+#[tokio::test]
+async fn close_pool_without_stakes() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        reward_multiplier: 0,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        stake_pool_token_account,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+    let destination = Pubkey::new_unique();
+
+    let (transaction, _) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        StakeInput {
+            amount: 1000,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::close_pool(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &destination,
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::ClosePoolMustHaveNoStakes,
+    );
+
+    warp_seconds(&mut client, 2 * hour).await;
+    let transaction = transactions::unstake(
+        &fee_payer,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet,
+        UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::close_pool(
+        &fee_payer,
+        &stake_pool,
+        &user_wallet,
+        &destination,
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let stake_pool_lamports = client.banks_client.get_balance(stake_pool).await.unwrap();
+    let token_account_lamports = client
+        .banks_client
+        .get_balance(stake_pool_token_account)
+        .await
+        .unwrap();
+    let transaction = transactions::close_pool(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &destination,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    client
+        .banks_client
+        .get_account_data_with_borsh::<ViewerStakePool>(stake_pool)
+        .await
+        .expect_err("pool was closed");
+    assert!(client
+        .banks_client
+        .get_account(stake_pool_token_account)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        client.banks_client.get_balance(destination).await.unwrap(),
+        stake_pool_lamports + token_account_lamports
+    );
+}
+
+#[tokio::test]
+async fn close_pool_with_not_counted_principal() {
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        mint,
+        stake_pool,
+        stake_pool_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [Rank::default(); 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+
+    // like principal of first version stake which was not migrated into totals
+    let transaction = spl_transactions::mint_to(
+        &fee_payer,
+        &mint.pubkey(),
+        &stake_pool_token_account,
+        &stake_pool_owner,
+        1000,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let stake_pool_state: ViewerStakePool = client
+        .banks_client
+        .get_account_data_with_borsh(stake_pool)
+        .await
+        .unwrap();
+    assert_eq!(stake_pool_state.total_staked, 0);
+
+    let transaction = transactions::close_pool(
+        &fee_payer,
+        &stake_pool,
+        &stake_pool_owner,
+        &Pubkey::new_unique(),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::ClosePoolMustHaveNoStakes,
+    );
+}
+
+#[tokio::test]
+async fn delegated_stake_from_user_token_account() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        reward_multiplier: 0,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(&stake_pool, &crate::stake_viewer_program_id());
+    let stake_input = |amount| StakeInput {
+        amount,
+        duration: hour,
+        position: 0,
+    };
+
+    let (transaction, _) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        stake_input(600),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::DelegatedStakeMustBeApprovedToStakeAuthority,
+    );
+
+    let transaction = spl_transactions::approve(
+        &fee_payer,
+        &user_token_account,
+        &stake_authority,
+        &user_wallet,
+        600,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, _) = transactions::delegated_stake(
+        &fee_payer,
+        &user_wallet,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        stake_input(600),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::StakePoolOwnerMustOwnStake,
+    );
+
+    let other_wallet = Keypair::new();
+    let (transaction, _) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &other_wallet.pubkey(),
+        stake_input(600),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::DelegatedStakeSourceMustBeOwnedByUserWallet,
+    );
+
+    let (transaction, _) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        stake_input(700),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::DelegatedStakeMustBeApprovedToStakeAuthority,
+    );
+
+    warp(&mut client, 100).await;
+    let (transaction, viewer_stake_account) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        stake_input(600),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 400);
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.owner, user_wallet.pubkey());
+    assert_eq!(viewer_stake_state.amount, 600);
+}
+
+#[tokio::test]
+async fn delegated_stake_keeps_lock_minimal() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        reward_multiplier: 0,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        stake_pool_owner,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(&stake_pool, &crate::stake_viewer_program_id());
+    let transaction = spl_transactions::approve(
+        &fee_payer,
+        &user_token_account,
+        &stake_authority,
+        &user_wallet,
+        1000,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, _) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        StakeInput {
+            amount: 600,
+            duration: 100 * hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::DelegatedStakeMustUseMinimalStakingTime,
+    );
+
+    let (transaction, viewer_stake_account) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        StakeInput {
+            amount: 600,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let staked: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+
+    // restake would reset lock of existing position
+    warp(&mut client, 100).await;
+    let (transaction, _) = transactions::delegated_stake(
+        &fee_payer,
+        &stake_pool_owner,
+        &stake_pool,
+        &user_token_account,
+        &user_wallet.pubkey(),
+        StakeInput {
+            amount: 400,
+            duration: hour,
+            position: 0,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::DelegatedStakeMustNotRestake,
+    );
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.staked_until, staked.staked_until);
+    assert_eq!(viewer_stake_state.amount, 600);
+}
+
+#[tokio::test]
+async fn stake_and_unstake_with_intent() {
+    let hour = 60 * 60;
+    let rank = Rank {
+        minimal_staking_time: hour,
+        amount: 0,
+        reward_multiplier: 0,
+    };
+    let StakePoolTest {
+        mut client,
+        fee_payer,
+        user_wallet,
+        stake_pool,
+        user_token_account,
+        ..
+    } = setup_stake_pool(
+        InitializeStakePoolInput {
+            ranks: [rank; 4],
+            kyc_required: false,
+            reward_mint: Pubkey::default(),
+        },
+        1000,
+    )
+    .await;
+    let (stake_authority, _) =
+        Pubkey::find_program_address_for_pubkey(&stake_pool, &crate::stake_viewer_program_id());
+    let transaction = spl_transactions::approve(
+        &fee_payer,
+        &user_token_account,
+        &stake_authority,
+        &user_wallet,
+        1200,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let now = get_clock(&mut client).await.unix_timestamp;
+    let stake_intent = |nonce, valid_until| StakeIntentInput {
+        stake_pool,
+        token_account_source: user_token_account,
+        stake: StakeInput {
+            amount: 600,
+            duration: hour,
+            position: 0,
+        },
+        nonce,
+        valid_until,
+    };
+
+    let valid_until = now + 10 * hour;
+
+    let (transaction, _) = transactions::stake_with_intent(
+        &fee_payer,
+        &user_wallet,
+        stake_intent(0, now - 1),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::IntentExpired,
+    );
+
+    let (instruction, _) = crate::instruction::stake_with_intent(
+        &fee_payer.pubkey(),
+        &user_wallet.pubkey(),
+        stake_intent(0, valid_until),
+    )
+    .unwrap();
+    let signature = user_wallet.sign_message(&instruction.data);
+    let verify = sator_sdk::ed25519::instruction(
+        &user_wallet.pubkey(),
+        &signature.as_ref().try_into().unwrap(),
+        &instruction.data,
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify, instruction],
+        Some(&fee_payer.pubkey()),
+        &[&fee_payer],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::IntentMustBeSignedByUserWallet,
+    );
+
+    let other_wallet = Keypair::new();
+    let (instruction, _) = crate::instruction::stake_with_intent(
+        &fee_payer.pubkey(),
+        &user_wallet.pubkey(),
+        stake_intent(0, valid_until),
+    )
+    .unwrap();
+    let message = crate::instruction::intent_message(
+        &stake_viewer_program_id(),
+        &stake_pool,
+        &instruction.data,
+    );
+    let signature = other_wallet.sign_message(&message);
+    let verify = sator_sdk::ed25519::instruction(
+        &other_wallet.pubkey(),
+        &signature.as_ref().try_into().unwrap(),
+        &message,
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify, instruction],
+        Some(&fee_payer.pubkey()),
+        &[&fee_payer],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::IntentMustBeSignedByUserWallet,
+    );
+
+    let (transaction, viewer_stake_account) = transactions::stake_with_intent(
+        &fee_payer,
+        &user_wallet,
+        stake_intent(0, valid_until),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let viewer_stake_state: ViewerStake = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_stake_account)
+        .await
+        .unwrap();
+    assert_eq!(viewer_stake_state.amount, 600);
+    let (viewer_wallet, _) = ViewerWallet::derive(
+        &stake_authority,
+        &user_wallet.pubkey(),
+        &stake_viewer_program_id(),
+    )
+    .unwrap();
+    let viewer_wallet_state: ViewerWallet = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_wallet)
+        .await
+        .unwrap();
+    assert_eq!(viewer_wallet_state.nonce, 1);
+
+    warp(&mut client, 100).await;
+    let (transaction, _) = transactions::stake_with_intent(
+        &fee_payer,
+        &user_wallet,
+        stake_intent(0, valid_until),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::IntentNonceMustMatchWallet,
+    );
+
+    warp_seconds(&mut client, 2 * hour).await;
+    let now = get_clock(&mut client).await.unix_timestamp;
+    let unstake_intent = |token_account_target| UnstakeIntentInput {
+        stake_pool,
+        token_account_target,
+        reward_token_account_target: token_account_target,
+        unstake: UnstakeInput {
+            amount: None,
+            position: 0,
+        },
+        nonce: 1,
+        valid_until: now + hour,
+    };
+
+    let mut instruction = crate::instruction::unstake_with_intent(
+        &fee_payer.pubkey(),
+        &user_wallet.pubkey(),
+        unstake_intent(user_token_account),
+    )
+    .unwrap();
+    let message = crate::instruction::intent_message(
+        &stake_viewer_program_id(),
+        &stake_pool,
+        &instruction.data,
+    );
+    let signature = user_wallet.sign_message(&message);
+    let verify = sator_sdk::ed25519::instruction(
+        &user_wallet.pubkey(),
+        &signature.as_ref().try_into().unwrap(),
+        &message,
+    );
+    let (other_token_account_transaction, other_token_account) =
+        spl_transactions::create_token_account(
+            10000000,
+            &get_token_account_state(&mut client.banks_client, &user_token_account)
+                .await
+                .mint,
+            &other_wallet,
+            &fee_payer,
+            client.last_blockhash,
+        );
+    client
+        .banks_client
+        .process_transaction(other_token_account_transaction)
+        .await
+        .unwrap();
+    instruction.accounts[5].pubkey = other_token_account.pubkey();
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify, instruction],
+        Some(&fee_payer.pubkey()),
+        &[&fee_payer],
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::IntentMustMatchAccounts,
+    );
+
+    let transaction = transactions::unstake_with_intent(
+        &fee_payer,
+        &user_wallet,
+        unstake_intent(user_token_account),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_token_account).await;
+    assert_eq!(user_token_account_state.amount, 1000);
+    client
+        .banks_client
+        .get_account_data_with_borsh::<ViewerStake>(viewer_stake_account)
+        .await
+        .expect_err("account was burned");
+
+    let (transaction, _) = transactions::stake_with_intent(
+        &fee_payer,
+        &user_wallet,
+        stake_intent(0, valid_until),
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::IntentNonceMustMatchWallet,
+    );
+    let viewer_wallet_state: ViewerWallet = client
+        .banks_client
+        .get_account_data_with_borsh(viewer_wallet)
+        .await
+        .unwrap();
+    assert_eq!(viewer_wallet_state.nonce, 2);
+    assert_eq!(viewer_wallet_state.total_staked, 0);
+}
@@ -1,3 +1,5 @@
+use std::convert::TryInto;
+
 use sator_sdk::types::*;
 use solana_program::{clock::Clock, system_instruction};
 use solana_program_test::*;
@@ -8,7 +10,10 @@ use solana_sdk::{
 };
 
 use crate::{
-    instruction::{InitializeStakePoolInput, StakeInput},
+    instruction::{
+        AddToStakeInput, ExtendLockInput, InitializeStakePoolInput, ProposeOwnerInput, RewardsInput,
+        SetEarlyUnstakePenaltyInput, SetPoolModeInput, SetStakeLimitsInput, SetViewerKycInput, StakeInput, StakeIntentInput, UnstakeInput, UnstakeIntentInput, UpdateRanksInput,
+    },
 };
 
 pub fn initialize_stake_pool(
@@ -56,19 +61,118 @@ pub fn stake(
     (transaction, stake)
 }
 
+pub fn delegated_stake(
+    fee_payer: &Keypair,
+    stake_pool_owner: &Keypair,
+    stake_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    user_wallet: &Pubkey,
+    input: StakeInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> (Transaction, Pubkey) {
+    let (instruction, stake) = crate::instruction::delegated_stake(
+        &fee_payer.pubkey(),
+        stake_pool,
+        token_account_source,
+        user_wallet,
+        &stake_pool_owner.pubkey(),
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    (transaction, stake)
+}
+
+/// Ed25519 instruction verifying `user_wallet` signature of intent message of `instruction` followed by `instruction`
+fn with_intent_signed_by(
+    user_wallet: &Keypair,
+    stake_pool: &Pubkey,
+    instruction: solana_program::instruction::Instruction,
+) -> [solana_program::instruction::Instruction; 2] {
+    let message =
+        crate::instruction::intent_message(&instruction.program_id, stake_pool, &instruction.data);
+    let signature = user_wallet.sign_message(&message);
+    let verify = sator_sdk::ed25519::instruction(
+        &user_wallet.pubkey(),
+        &signature.as_ref().try_into().unwrap(),
+        &message,
+    );
+    [verify, instruction]
+}
+
+pub fn stake_with_intent(
+    fee_payer: &Keypair,
+    user_wallet: &Keypair,
+    input: StakeIntentInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> (Transaction, Pubkey) {
+    let stake_pool = input.stake_pool;
+    let (instruction, stake) =
+        crate::instruction::stake_with_intent(&fee_payer.pubkey(), &user_wallet.pubkey(), input)
+            .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(
+        &with_intent_signed_by(user_wallet, &stake_pool, instruction),
+        Some(&fee_payer.pubkey()),
+    );
+    transaction.sign(&[fee_payer], recent_blockhash);
+    (transaction, stake)
+}
+
+pub fn unstake_with_intent(
+    fee_payer: &Keypair,
+    user_wallet: &Keypair,
+    input: UnstakeIntentInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let stake_pool = input.stake_pool;
+    let instruction =
+        crate::instruction::unstake_with_intent(&fee_payer.pubkey(), &user_wallet.pubkey(), input)
+            .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(
+        &with_intent_signed_by(user_wallet, &stake_pool, instruction),
+        Some(&fee_payer.pubkey()),
+    );
+    transaction.sign(&[fee_payer], recent_blockhash);
+    transaction
+}
+
 pub fn unstake(
     fee_payer: &Keypair,
     stake_pool: &Pubkey,
     token_account_target: &TokenAccountPubkey,
     user_wallet: &Keypair,
+    input: UnstakeInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    unstake_with_reward_target(
+        fee_payer,
+        stake_pool,
+        token_account_target,
+        token_account_target,
+        user_wallet,
+        input,
+        recent_blockhash,
+    )
+}
+
+pub fn unstake_with_reward_target(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    token_account_target: &TokenAccountPubkey,
+    reward_token_account_target: &TokenAccountPubkey,
+    user_wallet: &Keypair,
+    input: UnstakeInput,
     recent_blockhash: solana_program::hash::Hash,
 ) -> Transaction {
     let instruction = crate::instruction::unstake(
         stake_pool,
         token_account_target,
+        reward_token_account_target,
         &user_wallet.pubkey(),
         &fee_payer.pubkey(),
-        &None,        
+        &None,
+        input,
     )
     .expect("could create derived keys");
     let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
@@ -76,6 +180,266 @@ pub fn unstake(
     transaction
 }
 
+pub fn get_rank(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    user_wallet: &Pubkey,
+    positions: &[u8],
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::get_rank(stake_pool, user_wallet, positions)
+        .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer], recent_blockhash);
+    transaction
+}
+
+pub fn update_ranks(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    input: UpdateRanksInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::update_ranks(stake_pool, &stake_pool_owner.pubkey(), input)
+            .expect("could create instruction");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn set_pool_mode(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    input: SetPoolModeInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::set_pool_mode(stake_pool, &stake_pool_owner.pubkey(), input)
+            .expect("could create instruction");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn set_stake_limits(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    input: SetStakeLimitsInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::set_stake_limits(stake_pool, &stake_pool_owner.pubkey(), input)
+            .expect("could create instruction");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn close_pool(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    destination: &Pubkey,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::close_pool(stake_pool, &stake_pool_owner.pubkey(), destination)
+            .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn propose_owner(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    input: ProposeOwnerInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::propose_owner(stake_pool, &stake_pool_owner.pubkey(), input)
+            .expect("could create instruction");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn accept_owner(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    pending_owner: &Keypair,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::accept_owner(stake_pool, &pending_owner.pubkey())
+        .expect("could create instruction");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, pending_owner], recent_blockhash);
+    transaction
+}
+
+pub fn set_viewer_kyc(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    user_wallet: &Pubkey,
+    stake_pool_owner: &Keypair,
+    input: SetViewerKycInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::set_viewer_kyc(
+        &fee_payer.pubkey(),
+        stake_pool,
+        user_wallet,
+        &stake_pool_owner.pubkey(),
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn set_early_unstake_penalty(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    treasury: &TokenAccountPubkey,
+    input: SetEarlyUnstakePenaltyInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::set_early_unstake_penalty(
+        stake_pool,
+        &stake_pool_owner.pubkey(),
+        treasury,
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn early_unstake(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    token_account_target: &TokenAccountPubkey,
+    user_wallet: &Keypair,
+    treasury: &TokenAccountPubkey,
+    input: UnstakeInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::early_unstake(
+        stake_pool,
+        token_account_target,
+        &user_wallet.pubkey(),
+        &fee_payer.pubkey(),
+        treasury,
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[user_wallet, fee_payer], recent_blockhash);
+    transaction
+}
+
+pub fn add_to_stake(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    user_wallet: &Pubkey,
+    source_authority: &Keypair,
+    input: AddToStakeInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::add_to_stake(
+        &fee_payer.pubkey(),
+        stake_pool,
+        token_account_source,
+        user_wallet,
+        &source_authority.pubkey(),
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, source_authority], recent_blockhash);
+    transaction
+}
+
+pub fn fund_rewards(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    mint: &MintPubkey,
+    token_account_source: &TokenAccountPubkey,
+    source_authority: &Keypair,
+    input: RewardsInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::fund_rewards(
+        &fee_payer.pubkey(),
+        stake_pool,
+        mint,
+        token_account_source,
+        &source_authority.pubkey(),
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, source_authority], recent_blockhash);
+    transaction
+}
+
+pub fn withdraw_unallocated_rewards(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    stake_pool_owner: &Keypair,
+    token_account_target: &TokenAccountPubkey,
+    input: RewardsInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::withdraw_unallocated_rewards(
+        stake_pool,
+        &stake_pool_owner.pubkey(),
+        token_account_target,
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, stake_pool_owner], recent_blockhash);
+    transaction
+}
+
+pub fn migrate(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    viewer_stake_accounts: &[Pubkey],
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::migrate(&fee_payer.pubkey(), stake_pool, viewer_stake_accounts)
+            .expect("could create instruction");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer], recent_blockhash);
+    transaction
+}
+
+pub fn extend_lock(
+    fee_payer: &Keypair,
+    stake_pool: &Pubkey,
+    user_wallet: &Keypair,
+    input: ExtendLockInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::extend_lock(stake_pool, &user_wallet.pubkey(), input)
+        .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, user_wallet], recent_blockhash);
+    transaction
+}
+
 pub fn create_system_account(
     payer: &Keypair,
     account: &Keypair,
@@ -7,8 +7,31 @@ pub struct Rank {
 
     /// amount of token required to reach this rank
     pub amount: TokenAmount,
+
+    /// APY in [Rank::ONE] units, so `Rank::ONE` pays whole staked amount for a year of stake
+    pub reward_multiplier: u64,
+}
+
+/// [Rank] layout of [sator_sdk::state::StateVersion::V1]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default, Clone, Copy)]
+pub struct RankV1 {
+    pub minimal_staking_time: ApproximateSeconds,
+    pub amount: TokenAmount,
+}
+
+impl From<RankV1> for Rank {
+    fn from(rank: RankV1) -> Self {
+        Self {
+            minimal_staking_time: rank.minimal_staking_time,
+            amount: rank.amount,
+            reward_multiplier: 0,
+        }
+    }
 }
 
 impl Rank {
     pub const ONE: u128 = 10_000;
+
+    /// year used to prorate APY
+    pub const YEAR: ApproximateSeconds = 365 * 24 * 60 * 60;
 }
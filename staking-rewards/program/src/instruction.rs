@@ -0,0 +1,313 @@
+//! Program instruction state
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use sator_sdk::program::PubkeyPatterns;
+use sator_sdk::types::{
+    ApproximateSeconds, MintPubkey, SignerPubkey, TokenAccountPubkey, TokenAmount,
+};
+use solana_program::instruction::AccountMeta;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+
+use crate::{staking_rewards_program_id, state::StakingPool};
+
+#[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct StakeInput {
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct WithdrawInput {
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct NotifyRewardAmountInput {
+    /// reward tokens added to the pool for the new period
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct SetRewardDurationInput {
+    pub reward_duration: ApproximateSeconds,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum Instruction {
+    ///Like: 0u8
+    InitializeStakingPool,
+    ///Like: 1u8(u64)
+    Stake(StakeInput),
+    ///Like: 2u8(u64)
+    Withdraw(WithdrawInput),
+    ///Like: 3u8
+    GetReward,
+    ///Like: 4u8(u64)
+    NotifyRewardAmount(NotifyRewardAmountInput),
+    ///Like: 5u8(i64)
+    SetRewardDuration(SetRewardDurationInput),
+}
+
+fn derive_accounts(staking_pool: &Pubkey) -> Result<(Pubkey, Pubkey, Pubkey), ProgramError> {
+    let (pool_authority, _) =
+        Pubkey::find_program_address_for_pubkey(staking_pool, &staking_rewards_program_id());
+    let stake_token_account = Pubkey::create_with_seed(
+        &pool_authority,
+        StakingPool::STAKE_TOKEN_ACCOUNT,
+        &spl_token::id(),
+    )?;
+    let reward_token_account = Pubkey::create_with_seed(
+        &pool_authority,
+        StakingPool::REWARD_TOKEN_ACCOUNT,
+        &spl_token::id(),
+    )?;
+    Ok((pool_authority, stake_token_account, reward_token_account))
+}
+
+/// Creates [Instruction::InitializeStakingPool] instruction which initializes `staking_pool`, `stake_token_account` and `reward_token_account`.
+/// Reward period is [StakingPool::DEFAULT_REWARD_DURATION].
+///
+/// Accounts:
+///  * `system_program`       - *program, implicit* to create accounts
+///  * `sysvar_rent`          - *program, implicit* ensure that token accounts and `staking_pool` are rent exempt.
+///  * `spl_token`            - *program, implicit* spl token program to initialize token accounts.
+///  * `fee_payer`            - *signer, payer* pays for account creation
+///  * `owner`                - *signer* owner of `staking_pool`.
+///  * `staking_pool`         - *mutable, signer* not initialized not created account for pool data.
+///  * `pool_authority`       - *implicit, derived* program derived account from `32 bytes staking pool public key` based `program_id`.
+///  * `stake_token_account`  - *implicit, mutable, derived* not created account to create `spl_token` of `stake_mint` under `pool_authority`.
+///  * `reward_token_account` - *implicit, mutable, derived* not created account to create `spl_token` of `reward_mint` under `pool_authority`.
+///  * `stake_mint`           - mint of staked tokens
+///  * `reward_mint`          - mint of reward tokens
+pub fn initialize_staking_pool(
+    fee_payer: &SignerPubkey,
+    owner: &SignerPubkey,
+    staking_pool: &SignerPubkey,
+    stake_mint: &MintPubkey,
+    reward_mint: &MintPubkey,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (pool_authority, stake_token_account, reward_token_account) =
+        derive_accounts(staking_pool)?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::InitializeStakingPool,
+        vec![
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*fee_payer, true),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*staking_pool, true),
+            AccountMeta::new_readonly(pool_authority, false),
+            AccountMeta::new(stake_token_account, false),
+            AccountMeta::new(reward_token_account, false),
+            AccountMeta::new_readonly(*stake_mint, false),
+            AccountMeta::new_readonly(*reward_mint, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::Stake] instruction which accounts rewards earned so far and transfers `amount` from `token_account_source` to `stake_token_account`.
+///
+/// Accounts:
+///  * `system_program`       - *program, implicit*
+///  * `sysvar_rent`          - *program, implicit* to create `user_stake` which will be rent except if needed
+///  * `sysvar_clock`         - *program, implicit*
+///  * `spl_token`            - *program, implicit*
+///  * `fee_payer`            - *signer, payer* pays for account creation
+///  * `staking_pool`         - *mutable*
+///  * `pool_authority`       - *derived* as in [Instruction::InitializeStakingPool]
+///  * `user_stake`           - *implicit, derived, mutable* from `user_wallet` and `pool_authority`
+///  * `user_wallet`          - *signer* owner of `user_stake` and authority of `token_account_source`
+///  * `token_account_source` - *mutable* of stake mint
+///  * `stake_token_account`  - *derived, mutable, implicit*
+pub fn stake(
+    fee_payer: &SignerPubkey,
+    staking_pool: &Pubkey,
+    user_wallet: &SignerPubkey,
+    token_account_source: &TokenAccountPubkey,
+    input: StakeInput,
+) -> Result<(solana_program::instruction::Instruction, Pubkey), ProgramError> {
+    let (pool_authority, stake_token_account, _) = derive_accounts(staking_pool)?;
+    let (user_stake, _) = Pubkey::create_with_seed_for_pubkey(
+        &pool_authority,
+        user_wallet,
+        &staking_rewards_program_id(),
+    )?;
+    Ok((
+        solana_program::instruction::Instruction::new_with_borsh(
+            crate::id(),
+            &Instruction::Stake(input),
+            vec![
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new(*fee_payer, true),
+                AccountMeta::new(*staking_pool, false),
+                AccountMeta::new_readonly(pool_authority, false),
+                AccountMeta::new(user_stake, false),
+                AccountMeta::new_readonly(*user_wallet, true),
+                AccountMeta::new(*token_account_source, false),
+                AccountMeta::new(stake_token_account, false),
+            ],
+        ),
+        user_stake,
+    ))
+}
+
+/// Creates [Instruction::Withdraw] instruction which accounts rewards earned so far and transfers `amount` from `stake_token_account` to `token_account_target`.
+///
+/// Accounts:
+///  * `sysvar_clock`         - *program, implicit*
+///  * `spl_token`            - *program, implicit*
+///  * `staking_pool`         - *mutable*
+///  * `pool_authority`       - *derived* as in [Instruction::InitializeStakingPool]
+///  * `user_stake`           - *implicit, derived, mutable* from `user_wallet` and `pool_authority`
+///  * `user_wallet`          - *signer* owner of `user_stake`
+///  * `stake_token_account`  - *derived, mutable, implicit*
+///  * `token_account_target` - *mutable* any account of stake mint
+pub fn withdraw(
+    staking_pool: &Pubkey,
+    user_wallet: &SignerPubkey,
+    token_account_target: &TokenAccountPubkey,
+    input: WithdrawInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (pool_authority, stake_token_account, _) = derive_accounts(staking_pool)?;
+    let (user_stake, _) = Pubkey::create_with_seed_for_pubkey(
+        &pool_authority,
+        user_wallet,
+        &staking_rewards_program_id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::Withdraw(input),
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*staking_pool, false),
+            AccountMeta::new_readonly(pool_authority, false),
+            AccountMeta::new(user_stake, false),
+            AccountMeta::new_readonly(*user_wallet, true),
+            AccountMeta::new(stake_token_account, false),
+            AccountMeta::new(*token_account_target, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::GetReward] instruction which transfers all rewards earned so far from `reward_token_account` to `token_account_target`.
+///
+/// Accounts:
+///  * `sysvar_clock`         - *program, implicit*
+///  * `spl_token`            - *program, implicit*
+///  * `staking_pool`         - *mutable*
+///  * `pool_authority`       - *derived* as in [Instruction::InitializeStakingPool]
+///  * `user_stake`           - *implicit, derived, mutable* from `user_wallet` and `pool_authority`
+///  * `user_wallet`          - *signer* owner of `user_stake`
+///  * `reward_token_account` - *derived, mutable, implicit*
+///  * `token_account_target` - *mutable* any account of reward mint
+pub fn get_reward(
+    staking_pool: &Pubkey,
+    user_wallet: &SignerPubkey,
+    token_account_target: &TokenAccountPubkey,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (pool_authority, _, reward_token_account) = derive_accounts(staking_pool)?;
+    let (user_stake, _) = Pubkey::create_with_seed_for_pubkey(
+        &pool_authority,
+        user_wallet,
+        &staking_rewards_program_id(),
+    )?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::GetReward,
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*staking_pool, false),
+            AccountMeta::new_readonly(pool_authority, false),
+            AccountMeta::new(user_stake, false),
+            AccountMeta::new_readonly(*user_wallet, true),
+            AccountMeta::new(reward_token_account, false),
+            AccountMeta::new(*token_account_target, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::NotifyRewardAmount] instruction which transfers `amount` from `token_account_source` to `reward_token_account`,
+/// finishes current reward period and starts new one. Remaining rewards of current period are rated along new period.
+///
+/// Accounts:
+///  * `sysvar_clock`         - *program, implicit*
+///  * `spl_token`            - *program, implicit*
+///  * `staking_pool`         - *mutable*
+///  * `pool_authority`       - *derived* as in [Instruction::InitializeStakingPool]
+///  * `owner`                - *signer* owner of `staking_pool` and authority of `token_account_source`
+///  * `token_account_source` - *mutable* of reward mint
+///  * `reward_token_account` - *derived, mutable, implicit*
+pub fn notify_reward_amount(
+    staking_pool: &Pubkey,
+    owner: &SignerPubkey,
+    token_account_source: &TokenAccountPubkey,
+    input: NotifyRewardAmountInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    let (pool_authority, _, reward_token_account) = derive_accounts(staking_pool)?;
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::NotifyRewardAmount(input),
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*staking_pool, false),
+            AccountMeta::new_readonly(pool_authority, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*token_account_source, false),
+            AccountMeta::new(reward_token_account, false),
+        ],
+    ))
+}
+
+/// Creates [Instruction::SetRewardDuration] instruction which updates duration of next reward periods. Possible only after current period finished.
+///
+/// Accounts:
+///  * `sysvar_clock` - *program, implicit*
+///  * `staking_pool` - *mutable*
+///  * `owner`        - *signer* owner of `staking_pool`
+pub fn set_reward_duration(
+    staking_pool: &Pubkey,
+    owner: &SignerPubkey,
+    input: SetRewardDurationInput,
+) -> Result<solana_program::instruction::Instruction, ProgramError> {
+    Ok(solana_program::instruction::Instruction::new_with_borsh(
+        crate::id(),
+        &Instruction::SetRewardDuration(input),
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(*staking_pool, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Instruction, StakeInput, SetRewardDurationInput};
+    use borsh::*;
+
+    #[test]
+    fn test() {
+        let data = hex::encode(Instruction::Stake(StakeInput { amount: 42 }).try_to_vec().unwrap());
+        assert_eq!(data, "012a00000000000000");
+
+        let data = hex::encode(
+            Instruction::SetRewardDuration(SetRewardDurationInput {
+                reward_duration: 7 * 24 * 60 * 60,
+            })
+            .try_to_vec()
+            .unwrap(),
+        );
+        assert_eq!(data, "05803a090000000000");
+
+        let data = hex::encode(Instruction::GetReward.try_to_vec().unwrap());
+        assert_eq!(data, "03");
+    }
+}
@@ -0,0 +1,47 @@
+#![feature(trivial_bounds)]
+//! Continuous rewards for stakers, see `stake-viewer/program/syntetix.md`.
+//! - `StakingPool.owner` creates pool with stake mint and reward mint, mints can differ.
+//! - Staker can `Stake` and `Withdraw` any time, each call accounts rewards earned so far via `reward_per_token` accumulator.
+//! - Staker can `GetReward` any time.
+//! - `owner` brings rewards via `NotifyRewardAmount`. Each invocation finishes period and starts new one, remaining reward of previous period is rated along new period.
+//! - Reward period is same for all stakers, 7 days by default. `owner` can update it only after period finished.
+//! - `pool_authority` is derived from `staking_pool`, operations are signed by on chain derived signature.
+//! - `stake_token_account` and `reward_token_account` are derived from and owned by `pool_authority`.
+//! - `user_stake` for each user is derived from wallet and `pool_authority`.
+//!
+//!```rust, ignore
+//! let staking_pool = Pubkey::new_unique();
+//! let (pool_authority, _) = Pubkey::find_program_address(&[&staking_pool.to_bytes()[..32]], &staking_rewards_program_id());
+//! let stake_token_account = Pubkey::create_with_seed(
+//!     &pool_authority,
+//!     "StakingPool::stake_vault",
+//!     &spl_token::id(),
+//! );
+//! let reward_token_account = Pubkey::create_with_seed(
+//!     &pool_authority,
+//!     "StakingPool::reward_vault",
+//!     &spl_token::id(),
+//! );
+//! let seed = user_wallet.to_bytes();
+//! let seed = bs58::encode(&seed[..20]).into_string();
+//! let user_stake = Pubkey::create_with_seed(pool_authority, &seed, &staking_rewards_program_id());
+//!```
+
+pub mod entrypoint;
+pub mod errors;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+#[cfg(all(feature = "test-bpf", test))]
+mod tests;
+
+#[cfg(test)]
+mod transactions;
+
+use sator_sdk::types::ProgramPubkey;
+
+solana_program::declare_id!("EnevDL6tDGWErtigGNYw38KBQDgScyk4DTDZkHxm3861");
+
+pub fn staking_rewards_program_id() -> ProgramPubkey {
+    crate::id()
+}
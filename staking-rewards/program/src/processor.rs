@@ -0,0 +1,592 @@
+use sator_sdk::borsh::*;
+use sator_sdk::invoke::{self, ProgramPubkeySignature};
+use sator_sdk::state::StateVersion;
+use sator_sdk::types::*;
+use sator_sdk::{ensure, ensure_derived, ensure_eq, ensure_owner, program::*};
+use solana_program::clock::Clock;
+use solana_program::msg;
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_program::rent::Rent;
+use solana_program::sysvar::Sysvar;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
+
+use crate::errors;
+use crate::instruction::Instruction;
+use crate::state::{StakingPool, UserStake};
+
+// Program entrypoint's implementation
+pub fn process_instruction(
+    program_id: &ProgramPubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = Instruction::deserialize_const(instruction_data)?;
+    match instruction {
+        Instruction::InitializeStakingPool => {
+            msg!("Instruction::InitializeStakingPool");
+            match accounts {
+                [system_program, sysvar_rent, spl_token, fee_payer, owner, staking_pool, pool_authority, stake_token_account, reward_token_account, stake_mint, reward_mint, ..] => {
+                    initialize_staking_pool(
+                        program_id,
+                        system_program,
+                        sysvar_rent,
+                        spl_token,
+                        fee_payer,
+                        owner,
+                        staking_pool,
+                        pool_authority,
+                        stake_token_account,
+                        reward_token_account,
+                        stake_mint,
+                        reward_mint,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::Stake(input) => {
+            msg!("Instruction::Stake");
+            match accounts {
+                [system_program, sysvar_rent, sysvar_clock, spl_token, fee_payer, staking_pool, pool_authority, user_stake, user_wallet, token_account_source, stake_token_account, ..] => {
+                    stake(
+                        program_id,
+                        system_program,
+                        sysvar_rent,
+                        sysvar_clock,
+                        spl_token,
+                        fee_payer,
+                        staking_pool,
+                        pool_authority,
+                        user_stake,
+                        user_wallet,
+                        token_account_source,
+                        stake_token_account,
+                        input.amount,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::Withdraw(input) => {
+            msg!("Instruction::Withdraw");
+            match accounts {
+                [sysvar_clock, spl_token, staking_pool, pool_authority, user_stake, user_wallet, stake_token_account, token_account_target, ..] => {
+                    withdraw(
+                        program_id,
+                        sysvar_clock,
+                        spl_token,
+                        staking_pool,
+                        pool_authority,
+                        user_stake,
+                        user_wallet,
+                        stake_token_account,
+                        token_account_target,
+                        input.amount,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::GetReward => {
+            msg!("Instruction::GetReward");
+            match accounts {
+                [sysvar_clock, spl_token, staking_pool, pool_authority, user_stake, user_wallet, reward_token_account, token_account_target, ..] => {
+                    get_reward(
+                        program_id,
+                        sysvar_clock,
+                        spl_token,
+                        staking_pool,
+                        pool_authority,
+                        user_stake,
+                        user_wallet,
+                        reward_token_account,
+                        token_account_target,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::NotifyRewardAmount(input) => {
+            msg!("Instruction::NotifyRewardAmount");
+            match accounts {
+                [sysvar_clock, spl_token, staking_pool, pool_authority, owner, token_account_source, reward_token_account, ..] => {
+                    notify_reward_amount(
+                        program_id,
+                        sysvar_clock,
+                        spl_token,
+                        staking_pool,
+                        pool_authority,
+                        owner,
+                        token_account_source,
+                        reward_token_account,
+                        input.amount,
+                    )
+                }
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        Instruction::SetRewardDuration(input) => {
+            msg!("Instruction::SetRewardDuration");
+            match accounts {
+                [sysvar_clock, staking_pool, owner, ..] => set_reward_duration(
+                    program_id,
+                    sysvar_clock,
+                    staking_pool,
+                    owner,
+                    input.reward_duration,
+                ),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+    }
+}
+
+/// `pool_authority` with its bump seed, `stake_token_account` and `reward_token_account`
+fn derive_accounts(
+    staking_pool: &AccountInfo,
+    program_id: &ProgramPubkey,
+) -> Result<(Pubkey, u8, Pubkey, Pubkey), ProgramError> {
+    let (pool_authority_pubkey, bump_seed) =
+        Pubkey::find_program_address_for_pubkey(&staking_pool.pubkey(), program_id);
+    let stake_token_account_pubkey = Pubkey::create_with_seed(
+        &pool_authority_pubkey,
+        StakingPool::STAKE_TOKEN_ACCOUNT,
+        &spl_token::id(),
+    )?;
+    let reward_token_account_pubkey = Pubkey::create_with_seed(
+        &pool_authority_pubkey,
+        StakingPool::REWARD_TOKEN_ACCOUNT,
+        &spl_token::id(),
+    )?;
+    Ok((
+        pool_authority_pubkey,
+        bump_seed,
+        stake_token_account_pubkey,
+        reward_token_account_pubkey,
+    ))
+}
+
+fn read_staking_pool(
+    program_id: &ProgramPubkey,
+    staking_pool: &AccountInfo,
+) -> Result<StakingPool, ProgramError> {
+    ensure_eq!(
+        program_id,
+        staking_pool.owner,
+        errors::Error::StakingPoolMustBeOwnedByThisContract
+    );
+    let state = staking_pool.deserialize::<StakingPool>()?;
+    state.initialized()?;
+    Ok(state)
+}
+
+fn read_user_stake(
+    program_id: &ProgramPubkey,
+    pool_authority: &Pubkey,
+    user_stake: &AccountInfo,
+    user_wallet: &AccountInfo,
+) -> Result<UserStake, ProgramError> {
+    let (user_stake_pubkey, _) =
+        Pubkey::create_with_seed_for_pubkey(pool_authority, &user_wallet.pubkey(), program_id)?;
+    ensure_derived!(
+        user_stake_pubkey,
+        user_stake,
+        errors::Error::UserStakeMustBeDerivedFromUserWallet
+    );
+    ensure_eq!(
+        program_id,
+        user_stake.owner,
+        errors::Error::UserStakeMustBeOwnedByThisContract
+    );
+    let state = user_stake.deserialize::<UserStake>()?;
+    state.initialized()?;
+    ensure_owner!(
+        user_wallet,
+        state,
+        errors::Error::UserWalletMustBeOwnerOfUserStake
+    );
+    Ok(state)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn initialize_staking_pool<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    staking_pool: &AccountInfo<'a>,
+    pool_authority: &AccountInfo<'a>,
+    stake_token_account: &AccountInfo<'a>,
+    reward_token_account: &AccountInfo<'a>,
+    stake_mint: &AccountInfo<'a>,
+    reward_mint: &AccountInfo<'a>,
+) -> ProgramResult {
+    owner.is_signer()?;
+    staking_pool.is_signer()?;
+    let (pool_authority_pubkey, bump_seed, stake_token_account_pubkey, reward_token_account_pubkey) =
+        derive_accounts(staking_pool, program_id)?;
+
+    ensure_derived!(
+        pool_authority_pubkey,
+        pool_authority,
+        errors::Error::PoolAuthorityMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        stake_token_account_pubkey,
+        stake_token_account,
+        errors::Error::StakeTokenAccountMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        reward_token_account_pubkey,
+        reward_token_account,
+        errors::Error::RewardTokenAccountMustBeDerivedFromPool
+    );
+
+    let rent_state = Rent::from_account_info(sysvar_rent)?;
+    let lamports = rent_state.minimum_balance(StakingPool::LEN);
+    invoke::create_account(
+        fee_payer.clone(),
+        staking_pool.clone(),
+        lamports,
+        StakingPool::LEN as u64,
+        program_id,
+        system_program,
+        spl_token,
+    )?;
+
+    let authority_signature = ProgramPubkeySignature::new(staking_pool, bump_seed);
+    let lamports = rent_state.minimum_balance(spl_token::state::Account::LEN);
+    for (token_account, mint, seed) in [
+        (stake_token_account, stake_mint, StakingPool::STAKE_TOKEN_ACCOUNT),
+        (reward_token_account, reward_mint, StakingPool::REWARD_TOKEN_ACCOUNT),
+    ]
+    .iter()
+    {
+        invoke::create_account_with_seed_signed(
+            system_program,
+            fee_payer,
+            token_account,
+            pool_authority,
+            seed,
+            lamports,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+            &authority_signature,
+        )?;
+
+        invoke::initialize_token_account_signed(
+            token_account,
+            mint,
+            pool_authority,
+            sysvar_rent,
+            &authority_signature,
+        )?;
+    }
+
+    let mut state = staking_pool.deserialize::<StakingPool>()?;
+    state.uninitialized()?;
+    state.owner = owner.pubkey();
+    state.reward_duration = StakingPool::DEFAULT_REWARD_DURATION;
+    state.version = StateVersion::V1;
+    state.serialize_const(&mut *staking_pool.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn stake<'a>(
+    program_id: &ProgramPubkey,
+    system_program: &AccountInfo<'a>,
+    sysvar_rent: &AccountInfo<'a>,
+    sysvar_clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    fee_payer: &AccountInfo<'a>,
+    staking_pool: &AccountInfo<'a>,
+    pool_authority: &AccountInfo<'a>,
+    user_stake: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    token_account_source: &AccountInfo<'a>,
+    stake_token_account: &AccountInfo<'a>,
+    amount: TokenAmount,
+) -> ProgramResult {
+    user_wallet.is_signer()?;
+    ensure!(amount > 0, errors::Error::AmountMustBePositive);
+    let mut staking_pool_state = read_staking_pool(program_id, staking_pool)?;
+
+    let (pool_authority_pubkey, bump_seed, stake_token_account_pubkey, _) =
+        derive_accounts(staking_pool, program_id)?;
+    ensure_derived!(
+        pool_authority_pubkey,
+        pool_authority,
+        errors::Error::PoolAuthorityMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        stake_token_account_pubkey,
+        stake_token_account,
+        errors::Error::StakeTokenAccountMustBeDerivedFromPool
+    );
+
+    let mut user_stake_state = if user_stake.data_is_empty() {
+        let (user_stake_pubkey, seed) = Pubkey::create_with_seed_for_pubkey(
+            &pool_authority_pubkey,
+            &user_wallet.pubkey(),
+            program_id,
+        )?;
+        ensure_derived!(
+            user_stake_pubkey,
+            user_stake,
+            errors::Error::UserStakeMustBeDerivedFromUserWallet
+        );
+        let rent_state = Rent::from_account_info(sysvar_rent)?;
+        let lamports = rent_state.minimum_balance(UserStake::LEN);
+        let authority_signature = ProgramPubkeySignature::new(staking_pool, bump_seed);
+        invoke::create_account_with_seed_signed(
+            system_program,
+            fee_payer,
+            user_stake,
+            pool_authority,
+            &seed[..],
+            lamports,
+            UserStake::LEN as u64,
+            program_id,
+            &authority_signature,
+        )?;
+        UserStake {
+            version: StateVersion::V1,
+            owner: user_wallet.pubkey(),
+            ..Default::default()
+        }
+    } else {
+        read_user_stake(program_id, &pool_authority_pubkey, user_stake, user_wallet)?
+    };
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    staking_pool_state
+        .update_reward(Some(&mut user_stake_state), clock.unix_timestamp)
+        .ok_or::<ProgramError>(errors::Error::Overflow.into())?;
+    staking_pool_state.total_supply = staking_pool_state
+        .total_supply
+        .checked_add(amount)
+        .ok_or::<ProgramError>(errors::Error::Overflow.into())?;
+    user_stake_state.balance = user_stake_state
+        .balance
+        .checked_add(amount)
+        .ok_or::<ProgramError>(errors::Error::Overflow.into())?;
+
+    invoke::spl_token_transfer(
+        spl_token,
+        token_account_source,
+        stake_token_account,
+        user_wallet,
+        amount,
+    )?;
+
+    staking_pool_state.serialize_const(&mut *staking_pool.try_borrow_mut_data()?)?;
+    user_stake_state.serialize_const(&mut *user_stake.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn withdraw<'a>(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    staking_pool: &AccountInfo<'a>,
+    pool_authority: &AccountInfo<'a>,
+    user_stake: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    stake_token_account: &AccountInfo<'a>,
+    token_account_target: &AccountInfo<'a>,
+    amount: TokenAmount,
+) -> ProgramResult {
+    user_wallet.is_signer()?;
+    ensure!(amount > 0, errors::Error::AmountMustBePositive);
+    let mut staking_pool_state = read_staking_pool(program_id, staking_pool)?;
+
+    let (pool_authority_pubkey, bump_seed, stake_token_account_pubkey, _) =
+        derive_accounts(staking_pool, program_id)?;
+    ensure_derived!(
+        pool_authority_pubkey,
+        pool_authority,
+        errors::Error::PoolAuthorityMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        stake_token_account_pubkey,
+        stake_token_account,
+        errors::Error::StakeTokenAccountMustBeDerivedFromPool
+    );
+
+    let mut user_stake_state =
+        read_user_stake(program_id, &pool_authority_pubkey, user_stake, user_wallet)?;
+    ensure!(
+        amount <= user_stake_state.balance,
+        errors::Error::WithdrawAmountExceedsBalance
+    );
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    staking_pool_state
+        .update_reward(Some(&mut user_stake_state), clock.unix_timestamp)
+        .ok_or::<ProgramError>(errors::Error::Overflow.into())?;
+    staking_pool_state.total_supply -= amount;
+    user_stake_state.balance -= amount;
+
+    let authority_signature = ProgramPubkeySignature::new(staking_pool, bump_seed);
+    invoke::spl_token_transfer_signed(
+        spl_token,
+        stake_token_account,
+        token_account_target,
+        pool_authority,
+        amount,
+        &authority_signature,
+    )?;
+
+    staking_pool_state.serialize_const(&mut *staking_pool.try_borrow_mut_data()?)?;
+    user_stake_state.serialize_const(&mut *user_stake.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn get_reward<'a>(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    staking_pool: &AccountInfo<'a>,
+    pool_authority: &AccountInfo<'a>,
+    user_stake: &AccountInfo<'a>,
+    user_wallet: &AccountInfo<'a>,
+    reward_token_account: &AccountInfo<'a>,
+    token_account_target: &AccountInfo<'a>,
+) -> ProgramResult {
+    user_wallet.is_signer()?;
+    let mut staking_pool_state = read_staking_pool(program_id, staking_pool)?;
+
+    let (pool_authority_pubkey, bump_seed, _, reward_token_account_pubkey) =
+        derive_accounts(staking_pool, program_id)?;
+    ensure_derived!(
+        pool_authority_pubkey,
+        pool_authority,
+        errors::Error::PoolAuthorityMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        reward_token_account_pubkey,
+        reward_token_account,
+        errors::Error::RewardTokenAccountMustBeDerivedFromPool
+    );
+
+    let mut user_stake_state =
+        read_user_stake(program_id, &pool_authority_pubkey, user_stake, user_wallet)?;
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    staking_pool_state
+        .update_reward(Some(&mut user_stake_state), clock.unix_timestamp)
+        .ok_or::<ProgramError>(errors::Error::Overflow.into())?;
+
+    let reward = user_stake_state.rewards;
+    if reward > 0 {
+        user_stake_state.rewards = 0;
+        let authority_signature = ProgramPubkeySignature::new(staking_pool, bump_seed);
+        invoke::spl_token_transfer_signed(
+            spl_token,
+            reward_token_account,
+            token_account_target,
+            pool_authority,
+            reward,
+            &authority_signature,
+        )?;
+    }
+
+    staking_pool_state.serialize_const(&mut *staking_pool.try_borrow_mut_data()?)?;
+    user_stake_state.serialize_const(&mut *user_stake.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn notify_reward_amount<'a>(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo<'a>,
+    spl_token: &AccountInfo<'a>,
+    staking_pool: &AccountInfo<'a>,
+    pool_authority: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    token_account_source: &AccountInfo<'a>,
+    reward_token_account: &AccountInfo<'a>,
+    amount: TokenAmount,
+) -> ProgramResult {
+    owner.is_signer()?;
+    let mut staking_pool_state = read_staking_pool(program_id, staking_pool)?;
+    ensure_owner!(
+        owner,
+        staking_pool_state,
+        errors::Error::OwnerMustOwnStakingPool
+    );
+
+    let (pool_authority_pubkey, _, _, reward_token_account_pubkey) =
+        derive_accounts(staking_pool, program_id)?;
+    ensure_derived!(
+        pool_authority_pubkey,
+        pool_authority,
+        errors::Error::PoolAuthorityMustBeDerivedFromPool
+    );
+    ensure_derived!(
+        reward_token_account_pubkey,
+        reward_token_account,
+        errors::Error::RewardTokenAccountMustBeDerivedFromPool
+    );
+
+    invoke::spl_token_transfer(
+        spl_token,
+        token_account_source,
+        reward_token_account,
+        owner,
+        amount,
+    )?;
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    staking_pool_state
+        .notify_reward_amount(amount, clock.unix_timestamp)
+        .ok_or::<ProgramError>(errors::Error::Overflow.into())?;
+
+    // rewards of new period must be backed by tokens on reward account
+    let reward_token_account_state =
+        spl_token::state::Account::unpack(&reward_token_account.try_borrow_data()?)?;
+    ensure!(
+        staking_pool_state.is_reward_rate_covered(reward_token_account_state.amount),
+        errors::Error::RewardTooHigh
+    );
+
+    staking_pool_state.serialize_const(&mut *staking_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+fn set_reward_duration(
+    program_id: &ProgramPubkey,
+    sysvar_clock: &AccountInfo,
+    staking_pool: &AccountInfo,
+    owner: &AccountInfo,
+    reward_duration: ApproximateSeconds,
+) -> ProgramResult {
+    owner.is_signer()?;
+    let mut staking_pool_state = read_staking_pool(program_id, staking_pool)?;
+    ensure_owner!(
+        owner,
+        staking_pool_state,
+        errors::Error::OwnerMustOwnStakingPool
+    );
+    ensure!(
+        reward_duration > 0,
+        errors::Error::RewardDurationMustBePositive
+    );
+
+    let clock = Clock::from_account_info(sysvar_clock)?;
+    ensure!(
+        clock.unix_timestamp > staking_pool_state.period_finish,
+        errors::Error::RewardDurationCanBeUpdatedOnlyAfterPeriodFinished
+    );
+
+    staking_pool_state.reward_duration = reward_duration;
+    staking_pool_state.serialize_const(&mut *staking_pool.try_borrow_mut_data()?)?;
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! Program owned state
+use std::convert::TryFrom;
+
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use sator_sdk::state::StateVersion;
+use sator_sdk::types::{ApproximateSeconds, SignerPubkey, TokenAmount};
+use solana_program::clock::UnixTimestamp;
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError};
+
+/// Pool state and reward period
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct StakingPool {
+    pub version: StateVersion,
+    /// brings rewards and updates period duration
+    pub owner: SignerPubkey,
+    /// duration of each new reward period
+    pub reward_duration: ApproximateSeconds,
+    /// end of current reward period
+    pub period_finish: UnixTimestamp,
+    /// reward tokens per second in [StakingPool::PRECISION] units
+    pub reward_rate: u128,
+    pub last_update_time: UnixTimestamp,
+    /// reward per staked token accumulated up to `last_update_time` in [StakingPool::PRECISION] units
+    pub reward_per_token_stored: u128,
+    /// total staked amount
+    pub total_supply: TokenAmount,
+}
+
+/// User stake account state
+#[repr(C)]
+#[derive(Debug, BorshDeserialize, BorshSerialize, BorshSchema, Default)]
+pub struct UserStake {
+    pub version: StateVersion,
+    /// user owner of stake
+    pub owner: SignerPubkey,
+    /// staked amount
+    pub balance: TokenAmount,
+    /// [StakingPool::reward_per_token_stored] when `rewards` were accounted last time
+    pub reward_per_token_paid: u128,
+    /// earned and not yet claimed rewards
+    pub rewards: TokenAmount,
+}
+
+impl StakingPool {
+    pub const LEN: usize = 97;
+    pub const STAKE_TOKEN_ACCOUNT: &'static str = "StakingPool::stake_vault";
+    pub const REWARD_TOKEN_ACCOUNT: &'static str = "StakingPool::reward_vault";
+    pub const DEFAULT_REWARD_DURATION: ApproximateSeconds = 7 * 24 * 60 * 60;
+    pub const PRECISION: u128 = 1_000_000_000_000;
+
+    pub fn uninitialized(&self) -> ProgramResult {
+        if self.version == StateVersion::Uninitialized {
+            Ok(())
+        } else {
+            Err(ProgramError::AccountAlreadyInitialized)
+        }
+    }
+    /// Error if not initialized
+    pub fn initialized(&self) -> ProgramResult {
+        if self.version != StateVersion::Uninitialized {
+            Ok(())
+        } else {
+            Err(ProgramError::UninitializedAccount)
+        }
+    }
+
+    pub fn last_time_reward_applicable(&self, now: UnixTimestamp) -> UnixTimestamp {
+        now.min(self.period_finish)
+    }
+
+    /// `None` on overflow
+    pub fn reward_per_token(&self, now: UnixTimestamp) -> Option<u128> {
+        if self.total_supply == 0 {
+            return Some(self.reward_per_token_stored);
+        }
+        let elapsed = (self.last_time_reward_applicable(now) - self.last_update_time).max(0) as u128;
+        self.reward_per_token_stored
+            .checked_add(elapsed.checked_mul(self.reward_rate)? / self.total_supply as u128)
+    }
+
+    /// rewards of `user` not yet claimed, `None` on overflow
+    pub fn earned(&self, user: &UserStake, now: UnixTimestamp) -> Option<TokenAmount> {
+        let reward_per_token = self
+            .reward_per_token(now)?
+            .checked_sub(user.reward_per_token_paid)?;
+        let earned = (user.balance as u128).checked_mul(reward_per_token)? / Self::PRECISION;
+        TokenAmount::try_from(earned).ok()?.checked_add(user.rewards)
+    }
+
+    /// accounts rewards up to `now`, must be called before any change of balances
+    pub fn update_reward(&mut self, user: Option<&mut UserStake>, now: UnixTimestamp) -> Option<()> {
+        self.reward_per_token_stored = self.reward_per_token(now)?;
+        self.last_update_time = self.last_time_reward_applicable(now);
+        if let Some(user) = user {
+            user.rewards = self.earned(user, now)?;
+            user.reward_per_token_paid = self.reward_per_token_stored;
+        }
+        Some(())
+    }
+
+    /// finishes current period and starts new one with `reward` and remaining rewards of current period
+    pub fn notify_reward_amount(&mut self, reward: TokenAmount, now: UnixTimestamp) -> Option<()> {
+        self.update_reward(None, now)?;
+        let reward = (reward as u128).checked_mul(Self::PRECISION)?;
+        self.reward_rate = if now >= self.period_finish {
+            reward / self.reward_duration as u128
+        } else {
+            let remaining = (self.period_finish - now) as u128;
+            let leftover = remaining.checked_mul(self.reward_rate)?;
+            reward.checked_add(leftover)? / self.reward_duration as u128
+        };
+        self.last_update_time = now;
+        self.period_finish = now.checked_add(self.reward_duration)?;
+        Some(())
+    }
+
+    /// ensures that rewards of current period are backed by `balance` of reward tokens
+    pub fn is_reward_rate_covered(&self, balance: TokenAmount) -> bool {
+        self.reward_rate
+            .checked_mul(self.reward_duration as u128)
+            .map_or(false, |rewards| rewards <= balance as u128 * Self::PRECISION)
+    }
+}
+
+impl UserStake {
+    pub const LEN: usize = 65;
+
+    pub fn uninitialized(&self) -> ProgramResult {
+        if self.version == StateVersion::Uninitialized {
+            Ok(())
+        } else {
+            Err(ProgramError::AccountAlreadyInitialized)
+        }
+    }
+    /// Error if not initialized
+    pub fn initialized(&self) -> ProgramResult {
+        if self.version != StateVersion::Uninitialized {
+            Ok(())
+        } else {
+            Err(ProgramError::UninitializedAccount)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StakingPool, UserStake};
+    use borsh::*;
+
+    #[test]
+    fn test() {
+        let data = StakingPool::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), StakingPool::LEN);
+        let data = UserStake::default().try_to_vec().unwrap();
+        assert_eq!(data.len(), UserStake::LEN);
+    }
+
+    #[test]
+    fn rewards() {
+        let mut pool = StakingPool {
+            reward_duration: 100,
+            ..Default::default()
+        };
+        let mut alice = UserStake::default();
+        let mut bob = UserStake::default();
+
+        pool.update_reward(Some(&mut alice), 0).unwrap();
+        alice.balance += 100;
+        pool.total_supply += 100;
+
+        pool.notify_reward_amount(1000, 0).unwrap();
+        assert_eq!(pool.period_finish, 100);
+        assert_eq!(pool.earned(&alice, 50), Some(500));
+
+        pool.update_reward(Some(&mut bob), 50).unwrap();
+        bob.balance += 300;
+        pool.total_supply += 300;
+        assert_eq!(pool.earned(&bob, 50), Some(0));
+
+        // second half is shared 1 to 3, nothing is accrued after period finish
+        assert_eq!(pool.earned(&alice, 200), Some(625));
+        assert_eq!(pool.earned(&bob, 200), Some(375));
+
+        pool.update_reward(Some(&mut alice), 200).unwrap();
+        assert_eq!(alice.rewards, 625);
+
+        // leftover of current period is rated along new period
+        let mut pool = StakingPool {
+            reward_duration: 100,
+            total_supply: 100,
+            ..Default::default()
+        };
+        pool.notify_reward_amount(1000, 0).unwrap();
+        pool.notify_reward_amount(500, 50).unwrap();
+        assert_eq!(pool.period_finish, 150);
+        assert_eq!(pool.reward_rate, 10 * StakingPool::PRECISION);
+        assert!(pool.is_reward_rate_covered(1000));
+        assert!(!pool.is_reward_rate_covered(999));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    errors,
+    instruction::{NotifyRewardAmountInput, SetRewardDurationInput, StakeInput, WithdrawInput},
+    processor::process_instruction,
+    staking_rewards_program_id,
+    state::{StakingPool, UserStake},
+    transactions::{self, warp_seconds},
+};
+use solana_program::native_token::sol_to_lamports;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+
+use sator_sdk_test::{banks_client::*, spl_transactions};
+
+pub fn new_program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new(
+        "sator_staking_rewards",
+        staking_rewards_program_id(),
+        processor!(process_instruction),
+    );
+    program_test.add_program("spl_token", spl_token::id(), None);
+    program_test
+}
+
+#[tokio::test]
+async fn flow() {
+    let mut program_test = new_program_test();
+
+    let owner = Keypair::new();
+    let fee_payer = Keypair::new();
+    let user_wallet = Keypair::new();
+
+    program_test.add_account(
+        fee_payer.pubkey(),
+        Account {
+            lamports: u64::MAX / 32,
+            ..<_>::default()
+        },
+    );
+
+    let stake_mint = Keypair::new();
+    let reward_mint = Keypair::new();
+    let mut client = program_test.start_with_context().await;
+
+    for mint in [&stake_mint, &reward_mint].iter() {
+        let transaction = spl_transactions::create_initialize_mint(
+            &fee_payer,
+            mint,
+            &owner.pubkey(),
+            sol_to_lamports(10.),
+            2,
+            client.last_blockhash,
+        );
+        client
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    let minute = 60;
+    let hour = 60 * minute;
+
+    let (transaction, staking_pool) = transactions::initialize_staking_pool(
+        &fee_payer,
+        &owner,
+        &stake_mint.pubkey(),
+        &reward_mint.pubkey(),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let staking_pool_state: StakingPool = client
+        .banks_client
+        .get_account_data_with_borsh(staking_pool)
+        .await
+        .unwrap();
+    assert_eq!(staking_pool_state.owner, owner.pubkey());
+    assert_eq!(
+        staking_pool_state.reward_duration,
+        StakingPool::DEFAULT_REWARD_DURATION
+    );
+
+    let (transaction, user_stake_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &stake_mint.pubkey(),
+        &user_wallet,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let (transaction, user_reward_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &reward_mint.pubkey(),
+        &user_wallet,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let (transaction, owner_reward_token_account) = spl_transactions::create_token_account(
+        10000000,
+        &reward_mint.pubkey(),
+        &owner,
+        &fee_payer,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = spl_transactions::mint_to(
+        &fee_payer,
+        &stake_mint.pubkey(),
+        &user_stake_token_account.pubkey(),
+        &owner,
+        1000,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let transaction = spl_transactions::mint_to(
+        &fee_payer,
+        &reward_mint.pubkey(),
+        &owner_reward_token_account.pubkey(),
+        &owner,
+        100000,
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = transactions::set_reward_duration(
+        &fee_payer,
+        &owner,
+        &staking_pool,
+        SetRewardDurationInput {
+            reward_duration: 2 * hour,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (transaction, user_stake) = transactions::stake(
+        &fee_payer,
+        &user_wallet,
+        &staking_pool,
+        &user_stake_token_account.pubkey(),
+        StakeInput { amount: 1000 },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_stake_state: UserStake = client
+        .banks_client
+        .get_account_data_with_borsh(user_stake)
+        .await
+        .unwrap();
+    assert_eq!(user_stake_state.balance, 1000);
+    assert_eq!(user_stake_state.owner, user_wallet.pubkey());
+
+    let transaction = transactions::notify_reward_amount(
+        &fee_payer,
+        &owner,
+        &staking_pool,
+        &owner_reward_token_account.pubkey(),
+        NotifyRewardAmountInput { amount: 7200 },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let staking_pool_state: StakingPool = client
+        .banks_client
+        .get_account_data_with_borsh(staking_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        staking_pool_state.period_finish - staking_pool_state.last_update_time,
+        2 * hour
+    );
+    assert_eq!(staking_pool_state.total_supply, 1000);
+
+    warp_seconds(&mut client, hour).await;
+
+    let transaction = transactions::get_reward(
+        &fee_payer,
+        &user_wallet,
+        &staking_pool,
+        &user_reward_token_account.pubkey(),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let reward =
+        get_token_account_state(&mut client.banks_client, &user_reward_token_account.pubkey())
+            .await
+            .amount;
+    assert!(reward > 0);
+    assert!(reward < 7200);
+
+    let transaction = transactions::set_reward_duration(
+        &fee_payer,
+        &owner,
+        &staking_pool,
+        SetRewardDurationInput {
+            reward_duration: hour,
+        },
+        client.last_blockhash,
+    );
+    assert_program_error(
+        client.banks_client.process_transaction(transaction).await,
+        errors::Error::RewardDurationCanBeUpdatedOnlyAfterPeriodFinished,
+    );
+
+    warp_seconds(&mut client, 2 * hour).await;
+
+    let transaction = transactions::get_reward(
+        &fee_payer,
+        &user_wallet,
+        &staking_pool,
+        &user_reward_token_account.pubkey(),
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let reward =
+        get_token_account_state(&mut client.banks_client, &user_reward_token_account.pubkey())
+            .await
+            .amount;
+    assert_eq!(reward, 7200);
+
+    let transaction = transactions::withdraw(
+        &fee_payer,
+        &user_wallet,
+        &staking_pool,
+        &user_stake_token_account.pubkey(),
+        WithdrawInput { amount: 1000 },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_stake_token_account_state =
+        get_token_account_state(&mut client.banks_client, &user_stake_token_account.pubkey())
+            .await;
+    assert_eq!(user_stake_token_account_state.amount, 1000);
+
+    let transaction = transactions::set_reward_duration(
+        &fee_payer,
+        &owner,
+        &staking_pool,
+        SetRewardDurationInput {
+            reward_duration: hour,
+        },
+        client.last_blockhash,
+    );
+    client
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let staking_pool_state: StakingPool = client
+        .banks_client
+        .get_account_data_with_borsh(staking_pool)
+        .await
+        .unwrap();
+    assert_eq!(staking_pool_state.reward_duration, hour);
+    assert_eq!(staking_pool_state.total_supply, 0);
+}
@@ -0,0 +1,160 @@
+use sator_sdk::types::*;
+use solana_program::clock::Clock;
+use solana_program_test::*;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use crate::instruction::{
+    NotifyRewardAmountInput, SetRewardDurationInput, StakeInput, WithdrawInput,
+};
+
+pub fn initialize_staking_pool(
+    fee_payer: &Keypair,
+    owner: &Keypair,
+    stake_mint: &MintPubkey,
+    reward_mint: &MintPubkey,
+    recent_blockhash: solana_program::hash::Hash,
+) -> (Transaction, Pubkey) {
+    let staking_pool = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[crate::instruction::initialize_staking_pool(
+            &fee_payer.pubkey(),
+            &owner.pubkey(),
+            &staking_pool.pubkey(),
+            stake_mint,
+            reward_mint,
+        )
+        .expect("could create derived keys")],
+        Some(&fee_payer.pubkey()),
+    );
+    transaction.sign(&[fee_payer, owner, &staking_pool], recent_blockhash);
+    (transaction, staking_pool.pubkey())
+}
+
+pub fn stake(
+    fee_payer: &Keypair,
+    user_wallet: &Keypair,
+    staking_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    input: StakeInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> (Transaction, Pubkey) {
+    let (instruction, user_stake) = crate::instruction::stake(
+        &fee_payer.pubkey(),
+        staking_pool,
+        &user_wallet.pubkey(),
+        token_account_source,
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, user_wallet], recent_blockhash);
+    (transaction, user_stake)
+}
+
+pub fn withdraw(
+    fee_payer: &Keypair,
+    user_wallet: &Keypair,
+    staking_pool: &Pubkey,
+    token_account_target: &TokenAccountPubkey,
+    input: WithdrawInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::withdraw(
+        staking_pool,
+        &user_wallet.pubkey(),
+        token_account_target,
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, user_wallet], recent_blockhash);
+    transaction
+}
+
+pub fn get_reward(
+    fee_payer: &Keypair,
+    user_wallet: &Keypair,
+    staking_pool: &Pubkey,
+    token_account_target: &TokenAccountPubkey,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::get_reward(staking_pool, &user_wallet.pubkey(), token_account_target)
+            .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, user_wallet], recent_blockhash);
+    transaction
+}
+
+pub fn notify_reward_amount(
+    fee_payer: &Keypair,
+    owner: &Keypair,
+    staking_pool: &Pubkey,
+    token_account_source: &TokenAccountPubkey,
+    input: NotifyRewardAmountInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction = crate::instruction::notify_reward_amount(
+        staking_pool,
+        &owner.pubkey(),
+        token_account_source,
+        input,
+    )
+    .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, owner], recent_blockhash);
+    transaction
+}
+
+pub fn set_reward_duration(
+    fee_payer: &Keypair,
+    owner: &Keypair,
+    staking_pool: &Pubkey,
+    input: SetRewardDurationInput,
+    recent_blockhash: solana_program::hash::Hash,
+) -> Transaction {
+    let instruction =
+        crate::instruction::set_reward_duration(staking_pool, &owner.pubkey(), input)
+            .expect("could create derived keys");
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&fee_payer.pubkey()));
+    transaction.sign(&[fee_payer, owner], recent_blockhash);
+    transaction
+}
+
+pub async fn warp_seconds(program_context: &mut ProgramTestContext, seconds: ApproximateSeconds) {
+    let ticks_per_slot = program_context.genesis_config().ticks_per_slot();
+    assert_eq!(ticks_per_slot, 64);
+    assert!(
+        seconds as u64 > 10 * ticks_per_slot,
+        "clocks are very approximate"
+    );
+
+    let before = get_clock(program_context).await.unix_timestamp;
+    loop {
+        warp(program_context, 100).await;
+        let after = get_clock(program_context).await.unix_timestamp;
+        if after > before + seconds {
+            break;
+        }
+    }
+}
+
+pub async fn warp(program_context: &mut ProgramTestContext, slots: u64) {
+    let slot = program_context.banks_client.get_root_slot().await.unwrap();
+    program_context.warp_to_slot(slot + slots).unwrap();
+}
+
+pub async fn get_clock(program_context: &mut ProgramTestContext) -> Clock {
+    let clock = program_context
+        .banks_client
+        .get_account(solana_program::sysvar::clock::id())
+        .await
+        .unwrap()
+        .unwrap();
+    let clock: Clock = bincode::deserialize(&clock.data[..]).unwrap();
+    clock
+}